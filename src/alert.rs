@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Event {
+    /// A single host stopped answering while the link is still up.
+    HostDown,
+    Down,
+    Up,
+}
+
+impl Event {
+    /// Beeps used unless `--beeps` says otherwise, more for worse news.
+    fn default_beeps(&self) -> usize {
+        match *self {
+            Event::HostDown => 1,
+            Event::Down => 3,
+            Event::Up => 1,
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Event, ()> {
+        match s {
+            "host-down" => Ok(Event::HostDown),
+            "down" => Ok(Event::Down),
+            "up" => Ok(Event::Up),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Alert {
+    Beep,
+    Flash,
+    Both,
+    Silent,
+}
+
+impl FromStr for Alert {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Alert, ()> {
+        match s {
+            "beep" | "bell" => Ok(Alert::Beep),
+            "flash" => Ok(Alert::Flash),
+            "both" => Ok(Alert::Both),
+            "silent" | "none" => Ok(Alert::Silent),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Alert {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Alert::Beep => "bell",
+            Alert::Flash => "flash",
+            Alert::Both => "bell and flash",
+            Alert::Silent => "none",
+        }
+    }
+}
+
+/// How each event is signalled, with a fallback for events that have not
+/// been configured individually.
+pub struct Alerts {
+    events: HashMap<Event, Alert>,
+    pub default: Alert,
+    /// Number of beeps for each event, where not the default.
+    beeps: HashMap<Event, usize>,
+    /// Pause between the beeps of one alert.
+    pub beep_gap: Duration,
+}
+
+impl Alerts {
+    pub fn new() -> Alerts {
+        Alerts {
+            events: HashMap::new(),
+            default: Alert::Silent,
+            beeps: HashMap::new(),
+            beep_gap: Duration::from_millis(250),
+        }
+    }
+
+    pub fn insert(&mut self, event: Event, alert: Alert) {
+        self.events.insert(event, alert);
+    }
+
+    pub fn set_beeps(&mut self, event: Event, count: usize) {
+        self.beeps.insert(event, count);
+    }
+
+    pub fn beeps(&self, event: Event) -> usize {
+        self.beeps.get(&event).cloned().unwrap_or_else(|| event.default_beeps())
+    }
+
+    pub fn get(&self, event: Event) -> Alert {
+        self.events.get(&event).cloned().unwrap_or(self.default)
+    }
+}
+
+/// Parses an `EVENT=STYLE` pair such as `down=both`.
+pub fn parse(spec: &str) -> Option<(Event, Alert)> {
+    let mut parts = spec.splitn(2, '=');
+
+    let event = parts.next().and_then(|e| e.parse().ok());
+    let alert = parts.next().and_then(|a| a.parse().ok());
+
+    match (event, alert) {
+        (Some(event), Some(alert)) => Some((event, alert)),
+        _ => None,
+    }
+}
+
+/// Parses an `EVENT=COUNT` pair such as `down=3`.
+pub fn parse_beeps(spec: &str) -> Option<(Event, usize)> {
+    let mut parts = spec.splitn(2, '=');
+
+    let event = parts.next().and_then(|e| e.parse().ok());
+    let count = parts.next().and_then(|c| c.parse().ok());
+
+    match (event, count) {
+        (Some(event), Some(count)) => Some((event, count)),
+        _ => None,
+    }
+}
+
+/// Signals `event` in whatever way it is configured.
+pub fn fire(alerts: &Alerts, event: Event) {
+    let beeps = alerts.beeps(event);
+
+    match alerts.get(event) {
+        Alert::Beep => beep(beeps, alerts.beep_gap),
+        Alert::Flash => flash(),
+        Alert::Both => {
+            beep(beeps, alerts.beep_gap);
+            flash();
+        }
+        Alert::Silent => {}
+    }
+}
+
+/// Rings the bell `count` times, `gap` apart, on its own thread so the
+/// poll loop is not held up.
+fn beep(count: usize, gap: Duration) {
+    thread::spawn(move || {
+        let mut stderr = io::stderr();
+
+        for i in 0..count {
+            if i > 0 {
+                thread::sleep(gap);
+            }
+
+            let _ = stderr.write_all(b"\x07");
+            let _ = stderr.flush();
+        }
+    });
+}
+
+/// Briefly switches the terminal to reverse video.
+fn flash() {
+    thread::spawn(|| {
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(b"\x1b[?5h");
+        let _ = stderr.flush();
+
+        thread::sleep(Duration::from_millis(100));
+
+        let _ = stderr.write_all(b"\x1b[?5l");
+        let _ = stderr.flush();
+    });
+}
@@ -0,0 +1,71 @@
+use clock::SystemClock;
+use config::Config;
+use history::HostHistory;
+use render::{AnsiRenderer, Renderer};
+use slo;
+use std::collections::HashMap;
+use std::time::Duration;
+use time;
+use tracker::{State, TimeTracker};
+
+/// Feeds `iterations` synthetic polls of the configured hosts through the
+/// history, tracker and ANSI renderer, then prints the average time taken
+/// per frame and per statistic.
+pub fn run(config: &Config, iterations: usize) {
+    let mut history = HostHistory::new(SystemClock, config.ewma_alpha, config.history_size);
+    let mut tracker = TimeTracker::new(SystemClock);
+    let mut renderer = AnsiRenderer::new(config);
+    let labels = HashMap::new();
+    let hour = Duration::from_secs(60 * 60);
+
+    let mut timings = [0u64; 6];
+    let names = ["poll", "frame", "uptime_percentage", "summary", "worst", "p95"];
+
+    for i in 0..iterations {
+        let start = time::precise_time_ns();
+        let mut failed = 0;
+
+        for (h, host) in config.hosts.iter().enumerate() {
+            let dropped = (i + h) % 17 == 0;
+            let latency = 10.0 + ((i * 7 + h * 13) % 40) as f64;
+
+            history.update(host, dropped, latency);
+
+            if dropped {
+                failed += 1;
+            }
+        }
+
+        tracker.set_state(if failed == config.hosts.len() { State::Down } else { State::Up });
+        timings[0] += time::precise_time_ns() - start;
+
+        let start = time::precise_time_ns();
+        renderer.draw_stats(&tracker, &history, &labels);
+        renderer.draw_hosts(&config.hosts, &labels, &[], &history);
+        renderer.draw_outages(tracker.downtimes());
+        renderer.take_frame();
+        timings[1] += time::precise_time_ns() - start;
+
+        let start = time::precise_time_ns();
+        tracker.uptime_percentage();
+        timings[2] += time::precise_time_ns() - start;
+
+        let start = time::precise_time_ns();
+        history.summary(hour);
+        timings[3] += time::precise_time_ns() - start;
+
+        let start = time::precise_time_ns();
+        history.worst(config.worst_by, hour);
+        timings[4] += time::precise_time_ns() - start;
+
+        let start = time::precise_time_ns();
+        slo::percentile(&mut history.latencies(hour), 95.0);
+        timings[5] += time::precise_time_ns() - start;
+    }
+
+    println!("{} iterations over {} hosts", iterations, config.hosts.len());
+
+    for (name, total) in names.iter().zip(timings.iter()) {
+        println!("{:>18}: {:>10.2} µs", name, *total as f64 / iterations.max(1) as f64 / 1000.0);
+    }
+}
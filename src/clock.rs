@@ -0,0 +1,93 @@
+#[cfg(test)]
+use std::cell::Cell;
+use std::rc::Rc;
+#[cfg(test)]
+use std::time::Duration;
+use std::time::Instant;
+use time::{self, Timespec};
+
+/// Source of the current time, so time-dependent code can be driven
+/// deterministically.
+pub trait Clock {
+    /// Monotonic time, used for all durations.
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for timestamps and for calendar questions such as
+    /// whether the local date has changed. Never used to measure durations.
+    fn wall(&self) -> Timespec;
+}
+
+#[derive(Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn wall(&self) -> Timespec {
+        time::get_time()
+    }
+}
+
+impl<C: Clock> Clock for Rc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn wall(&self) -> Timespec {
+        (**self).wall()
+    }
+}
+
+/// A clock that only moves when told to.
+#[cfg(test)]
+pub struct MockClock {
+    now: Cell<Instant>,
+    wall: Cell<Timespec>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new() -> MockClock {
+        MockClock {
+            now: Cell::new(Instant::now()),
+            wall: Cell::new(Timespec::new(0, 0)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+        self.wall.set(self.wall.get() + time::Duration::from_std(by).unwrap());
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+
+    fn wall(&self) -> Timespec {
+        self.wall.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_moves_only_when_advanced() {
+        let clock = MockClock::new();
+        let (now, wall) = (clock.now(), clock.wall());
+
+        assert_eq!(clock.now(), now);
+        assert_eq!(clock.wall(), wall);
+
+        clock.advance(Duration::from_millis(1500));
+
+        assert_eq!(clock.now().duration_since(now), Duration::from_millis(1500));
+        assert_eq!(clock.wall(), Timespec::new(1, 500_000_000));
+    }
+}
@@ -0,0 +1,978 @@
+use alert::{self, Alerts};
+use service::Service;
+use samples::RAW_CAPACITY;
+use slo::LatencySlo;
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::time::Duration;
+use subnet;
+use time;
+use tracker::OUTAGE_BANDS;
+use {PING_TIMEOUT, POLL_INTERVAL};
+
+const DEFAULT_HOSTS: [&str; 3] = ["8.8.8.8", "4.2.2.2", "208.67.222.222"];
+
+/// Environment variables read as options, for deployments where setting
+/// the environment is easier than passing arguments or mounting a file.
+/// They override config files, and the command line overrides them.
+const ENV_OPTIONS: [(&str, &str); 8] = [
+    ("UPTIME_HOSTS", "--hosts"),
+    ("UPTIME_INTERVAL", "--interval"),
+    ("UPTIME_TIMEOUT", "--timeout"),
+    ("UPTIME_TITLE", "--title"),
+    ("UPTIME_INFLUX", "--influx"),
+    ("UPTIME_ON_DOWN", "--on-down"),
+    ("UPTIME_ON_UP", "--on-up"),
+    ("UPTIME_INCIDENT_REPORT", "--incident-report"),
+];
+
+/// Ways to get data out besides the log, each with the option that turns
+/// it on. `--capabilities` lists these, so every new output belongs here.
+pub const OUTPUTS: [(&str, &str); 6] = [
+    ("stream-json", "--stream-json"),
+    ("influx", "--influx"),
+    ("status-socket", "--status-socket"),
+    ("incident-report", "--incident-report"),
+    ("ics", "--ics-out"),
+    ("screen-file", "--screen-file"),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RendererKind {
+    Log,
+    Ansi,
+}
+
+/// Which events are written to the output streams.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LogMode {
+    Transitions,
+    Polls,
+    Both,
+}
+
+impl LogMode {
+    pub fn polls(&self) -> bool {
+        *self != LogMode::Transitions
+    }
+
+    pub fn transitions(&self) -> bool {
+        *self != LogMode::Polls
+    }
+}
+
+/// How many hosts must drop before the link counts as down.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DownWhen {
+    Any,
+    Majority,
+    All,
+}
+
+impl DownWhen {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DownWhen::Any => "any",
+            DownWhen::Majority => "majority",
+            DownWhen::All => "all",
+        }
+    }
+}
+
+/// What singles out the worst host.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WorstBy {
+    Loss,
+    /// 95th percentile latency.
+    Latency,
+}
+
+/// A span chosen with `--window` for every windowed statistic at once.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Window {
+    Minute,
+    FiveMinutes,
+    Hour,
+    Day,
+    /// Everything since startup.
+    Session,
+}
+
+impl Window {
+    pub fn span(&self) -> Duration {
+        match *self {
+            Window::Minute => Duration::from_secs(60),
+            Window::FiveMinutes => Duration::from_secs(5 * 60),
+            Window::Hour => Duration::from_secs(60 * 60),
+            Window::Day => Duration::from_secs(24 * 60 * 60),
+            Window::Session => Duration::from_secs(u64::MAX),
+        }
+    }
+
+    /// Describes the span for headings, e.g. "last hour".
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Window::Minute => "last minute",
+            Window::FiveMinutes => "last 5 minutes",
+            Window::Hour => "last hour",
+            Window::Day => "last 24 hours",
+            Window::Session => "session",
+        }
+    }
+}
+
+/// How much the statistics block shows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StatsLevel {
+    /// State and uptime only.
+    Compact,
+    Normal,
+    /// Adds loss and latency aggregated across all hosts.
+    Verbose,
+}
+
+pub struct Config {
+    /// Name for this session, shown in the header and included in exports.
+    pub title: String,
+    pub renderer: RendererKind,
+    pub hosts: Vec<String>,
+    pub hosts_file: Option<String>,
+    /// Seconds between polls.
+    pub interval: u64,
+    /// Seconds to wait for each echo reply.
+    pub timeout: f64,
+    /// Hostnames to monitor over both IPv4 and IPv6.
+    pub dual_stack: Vec<String>,
+    pub services: Vec<Service>,
+    /// Availability targets, as percentages, for individual hosts.
+    pub host_slas: HashMap<String, f64>,
+    /// Show and export hosts as `host-1`, `host-2` and so on.
+    pub redact: bool,
+    /// Hosts drawn at the top of the host list, in this order.
+    pub pinned: Vec<String>,
+    /// Ignored when services are configured; each service has its own
+    /// quorum instead.
+    pub down_when: DownWhen,
+    /// Drop malformed hosts with a warning instead of refusing to start.
+    pub skip_invalid_hosts: bool,
+    /// Count loopback hosts towards the link state.
+    pub count_local: bool,
+    pub ewma_alpha: f64,
+    /// Raw polls kept per host for analysis and charts.
+    pub history_size: usize,
+    /// Soft cap, in bytes, on the memory used by host history.
+    pub max_mem: Option<usize>,
+    pub latency_precision: usize,
+    /// Decimals shown in uptime percentages.
+    pub uptime_precision: usize,
+    /// Round uptime percentages down, so any downtime shows below 100%.
+    pub floor_uptime: bool,
+    /// `strftime` format for displayed timestamps.
+    pub time_format: String,
+    /// Latency, in ms, above which a responding host counts as degraded.
+    pub latency_down_threshold: Option<f64>,
+    pub latency_slo: Option<LatencySlo>,
+    pub on_down: Option<String>,
+    /// File to append a report to when an outage ends.
+    pub incident_report: Option<String>,
+    pub incident_template: Option<String>,
+    pub on_up: Option<String>,
+    pub burst_on_fail: usize,
+    pub self_check: bool,
+    /// Poll once, print a single status glyph and exit.
+    pub status_char: bool,
+    /// Time this many synthetic polls, print the results and exit.
+    pub bench: Option<usize>,
+    /// Two streams recorded with `--stream-json` to compare side by side
+    /// before exiting.
+    pub compare_sessions: Option<(String, String)>,
+    /// Print the version and exit.
+    pub version: bool,
+    /// Print what this build supports, as JSON, and exit.
+    pub capabilities: bool,
+    pub alerts: Alerts,
+    pub stream_json: bool,
+    /// InfluxDB write endpoint to push each poll to.
+    pub influx: Option<String>,
+    /// Run unattended: log only warnings and errors, never draw.
+    pub daemon: bool,
+    /// Where to write the process ID.
+    pub pid_file: Option<String>,
+    /// iCalendar file to keep up to date with one event per outage.
+    pub ics_out: Option<String>,
+    /// Unix socket to serve the current status on.
+    pub status_socket: Option<String>,
+    /// What `--stream-json` writes: one line per poll unless transitions
+    /// are asked for.
+    pub log_mode: LogMode,
+    pub histogram: bool,
+    /// Upper bounds, in seconds, of the outage duration buckets.
+    pub outage_bands: [u64; 3],
+    /// Seconds the link must stay up before an outage is considered over.
+    pub flap_settle: u64,
+    /// Seconds after startup during which failures are shown but not
+    /// tracked as outages.
+    pub startup_grace: u64,
+    pub stats: StatsLevel,
+    /// Half-life, in seconds, of the health score; it is shown only when
+    /// this is set.
+    pub health_half_life: Option<u64>,
+    /// Seconds covered by the recent availability row.
+    pub recent_window: u64,
+    pub worst_by: WorstBy,
+    /// Overrides the span of the recent availability row, worst host, mtr
+    /// columns and hourly summaries.
+    pub window: Option<Window>,
+    /// Minutes without a state change after which the dashboard dims.
+    pub dim_after: Option<u64>,
+    /// Draw hosts as a compact grid rather than one line each.
+    pub grid: bool,
+    /// Terminal columns to lay the ANSI output out for.
+    pub width: Option<usize>,
+    /// Draw a latency chart in place of the host list.
+    pub chart: bool,
+    /// Two hosts to show side by side.
+    pub compare: Option<(String, String)>,
+    /// Show this many recent readings per host in place of the host list.
+    pub readings: Option<usize>,
+    /// Show recent min/avg/max latency on each host row.
+    pub mtr_style: bool,
+    /// File kept up to date with a plain-text copy of the dashboard.
+    pub screen_file: Option<String>,
+    /// Most the poll interval is shifted either way, in ms.
+    pub jitter: u64,
+    /// Stop after this many seconds instead of running forever.
+    pub duration: Option<u64>,
+    /// Uptime percentage a timed run must reach to exit successfully.
+    pub min_uptime: Option<f64>,
+    /// Print a plain-text timeline of the session when a `--duration` run
+    /// ends.
+    pub exit_chart: bool,
+    /// Arguments this config was parsed from, replayed by `merge`.
+    args: Vec<String>,
+    /// Hosts from `--subnet`, which replace the host list in `finish`.
+    subnet_hosts: Vec<String>,
+    /// Whether `--hosts` has been given, so that the first list replaces
+    /// the default hosts.
+    hosts_given: bool,
+}
+
+impl Config {
+    /// Merges any `--config` files, then the `ENV_OPTIONS` environment
+    /// variables, then the rest of the command line. Files are merged in
+    /// the order given, so a later file overrides an earlier one, the
+    /// environment overrides files, and the command line overrides both.
+    /// List options, such as `--hosts` and `--service`, accumulate across
+    /// all of them instead.
+    pub fn from_args() -> Result<Config, String> {
+        let mut files = Vec::new();
+        let mut rest = Vec::new();
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                let path: String = parse_value(&arg, args.next())?;
+                let options = read_config_file(&path)?;
+
+                files.push((path, options));
+            }
+            else {
+                rest.push(arg);
+            }
+        }
+
+        Config::from_sources(files, |var| env::var(var).ok(), rest)
+    }
+
+    /// Merges `files`, each a path with the options read from it, then the
+    /// `ENV_OPTIONS` that `var` finds set, then `cli`, and checks the
+    /// result.
+    fn from_sources<F>(files: Vec<(String, Vec<String>)>, var: F, cli: Vec<String>)
+        -> Result<Config, String> where F: Fn(&str) -> Option<String> {
+        let mut config = Config::default();
+
+        for (path, options) in files {
+            let file = Config::layer(options)
+                .map_err(|e| format!("In config file {}: {}", path, e))?;
+
+            config = config.merge(file);
+        }
+
+        for &(name, option) in ENV_OPTIONS.iter() {
+            if let Some(value) = var(name) {
+                let layer = Config::layer(vec![option.to_string(), value])
+                    .map_err(|e| format!("In {}: {}", name, e))?;
+
+                config = config.merge(layer);
+            }
+        }
+
+        config.merge(Config::layer(cli)?).finish()
+    }
+
+    /// Parses the options from one source, such as a config file. Nothing
+    /// that depends on other options is checked until `finish`, as those
+    /// may come from another source.
+    pub fn layer<I: IntoIterator<Item = String>>(args: I) -> Result<Config, String> {
+        let mut config = Config::default();
+        let args: Vec<String> = args.into_iter().collect();
+        let mut rest = args.clone().into_iter();
+
+        while let Some(arg) = rest.next() {
+            config.apply(arg, &mut rest)?;
+        }
+
+        config.args = args;
+        Ok(config)
+    }
+
+    /// Layers `other` on top of this config: each option given to `other`
+    /// overrides the value here, except for list options such as `--hosts`,
+    /// `--service` and `--pin`, which add to the lists here.
+    pub fn merge(mut self, other: Config) -> Config {
+        let mut args = other.args.clone().into_iter();
+
+        while let Some(arg) = args.next() {
+            self.apply(arg, &mut args).expect("options that parsed once parse again");
+        }
+
+        self.args.extend(other.args);
+        self
+    }
+
+    /// Checks the merged options against each other and fills in the hosts
+    /// implied by services and subnets.
+    pub fn finish(mut self) -> Result<Config, String> {
+        if self.stream_json && self.renderer == RendererKind::Ansi {
+            return Err("--stream-json cannot be combined with --renderer ansi".to_string());
+        }
+
+        if self.daemon && self.renderer == RendererKind::Ansi {
+            return Err("--daemon cannot be combined with --renderer ansi".to_string());
+        }
+
+        if self.screen_file.is_some() && self.renderer != RendererKind::Ansi {
+            return Err("--screen-file requires --renderer ansi".to_string());
+        }
+
+        if self.hosts_file.is_some()
+            && (!self.services.is_empty() || !self.subnet_hosts.is_empty()) {
+            return Err("--hosts-file cannot be combined with --service or --subnet".to_string());
+        }
+
+        if self.hosts_file.is_some() && !self.dual_stack.is_empty() {
+            return Err("--hosts-file cannot be combined with --dual-stack".to_string());
+        }
+
+        if self.jitter > self.interval.saturating_mul(1000) {
+            return Err(format!("--jitter cannot exceed the {}s poll interval", self.interval));
+        }
+
+        if self.min_uptime.is_some() && self.duration.is_none() {
+            return Err("--min-uptime requires --duration".to_string());
+        }
+
+        if self.exit_chart && self.duration.is_none() {
+            return Err("--exit-chart requires --duration".to_string());
+        }
+
+        if !self.services.is_empty() || !self.subnet_hosts.is_empty() {
+            let service_hosts = self.services.iter().flat_map(|s| s.hosts.iter().cloned());
+
+            self.hosts.clear();
+
+            for host in service_hosts.chain(self.subnet_hosts.iter().cloned()) {
+                if !self.hosts.contains(&host) {
+                    self.hosts.push(host);
+                }
+            }
+        }
+
+        // Hosts from a hosts file or --dual-stack are only known once the
+        // monitor is running.
+        if self.hosts_file.is_none() && self.dual_stack.is_empty() {
+            if let Some((ref a, ref b)) = self.compare {
+                if let Some(host) = [a, b].iter().find(|host| !self.hosts.contains(host)) {
+                    return Err(format!("--compare names {}, which is not monitored", host));
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn apply<I: Iterator<Item = String>>(&mut self, arg: String, args: &mut I)
+        -> Result<(), String> {
+        match arg.as_str() {
+            "--ewma-alpha" => {
+                let alpha: f64 = parse_value(&arg, args.next())?;
+
+                if alpha <= 0.0 || alpha > 1.0 {
+                    return Err(format!("{} must be in (0, 1], got {}", arg, alpha));
+                }
+
+                self.ewma_alpha = alpha;
+            }
+            "--history-size" => {
+                let size: usize = parse_value(&arg, args.next())?;
+
+                if size == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+
+                self.history_size = size;
+            }
+            "--max-mem" => {
+                let mb: usize = parse_value(&arg, args.next())?;
+
+                if mb == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+
+                match mb.checked_mul(1024 * 1024) {
+                    Some(bytes) => self.max_mem = Some(bytes),
+                    None => return Err(format!("{} is too large: {} MB", arg, mb)),
+                }
+            }
+            "--latency-precision" => self.latency_precision = parse_value(&arg, args.next())?,
+            "--uptime-precision" => self.uptime_precision = parse_value(&arg, args.next())?,
+            "--floor-uptime" => self.floor_uptime = true,
+            "--time-format" => {
+                let format: String = parse_value(&arg, args.next())?;
+
+                time::strftime(&format, &time::now())
+                    .map_err(|e| format!("Invalid value for {}: {}", arg, e))?;
+
+                self.time_format = format;
+            }
+            "--latency-down-threshold" => {
+                self.latency_down_threshold = Some(parse_value(&arg, args.next())?);
+            }
+            "--latency-slo" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                self.latency_slo = Some(LatencySlo::parse(&spec)?);
+            }
+            "--incident-report" => self.incident_report = Some(parse_value(&arg, args.next())?),
+            "--incident-template" => {
+                self.incident_template = Some(parse_value(&arg, args.next())?);
+            }
+            "--on-down" => self.on_down = Some(parse_value(&arg, args.next())?),
+            "--on-up" => self.on_up = Some(parse_value(&arg, args.next())?),
+            "--burst-on-fail" => self.burst_on_fail = parse_value(&arg, args.next())?,
+            "--self-check" => self.self_check = true,
+            "--status-char" => self.status_char = true,
+            "--bench" => self.bench = Some(parse_value(&arg, args.next())?),
+            "--compare-sessions" => {
+                let a = parse_value(&arg, args.next())?;
+                self.compare_sessions = Some((a, parse_value(&arg, args.next())?));
+            }
+            "--version" => self.version = true,
+            "--capabilities" => self.capabilities = true,
+            "--title" => self.title = parse_value(&arg, args.next())?,
+            "--renderer" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.renderer = match value.as_str() {
+                    "log" => RendererKind::Log,
+                    "ansi" => RendererKind::Ansi,
+                    _ => return Err(format!("Unknown renderer: {}", value)),
+                };
+            }
+            "--stream-json" => self.stream_json = true,
+            "--influx" => self.influx = Some(parse_value(&arg, args.next())?),
+            "--daemon" => self.daemon = true,
+            "--pid-file" => self.pid_file = Some(parse_value(&arg, args.next())?),
+            "--ics-out" => self.ics_out = Some(parse_value(&arg, args.next())?),
+            "--status-socket" => self.status_socket = Some(parse_value(&arg, args.next())?),
+            "--log-mode" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.log_mode = match value.as_str() {
+                    "transitions" => LogMode::Transitions,
+                    "polls" => LogMode::Polls,
+                    "both" => LogMode::Both,
+                    _ => return Err(format!("Unknown log mode: {}", value)),
+                };
+            }
+            "--histogram" => self.histogram = true,
+            "--recent-window" => {
+                let secs: u64 = parse_value(&arg, args.next())?;
+
+                if secs == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+
+                self.recent_window = secs;
+            }
+            "--health-half-life" => {
+                let secs: u64 = parse_value(&arg, args.next())?;
+
+                if secs == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+
+                self.health_half_life = Some(secs);
+            }
+            "--window" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.window = Some(match value.as_str() {
+                    "1m" => Window::Minute,
+                    "5m" => Window::FiveMinutes,
+                    "1h" => Window::Hour,
+                    "24h" => Window::Day,
+                    "session" => Window::Session,
+                    _ => return Err(format!("Unknown window: {}", value)),
+                });
+            }
+            "--worst-by" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.worst_by = match value.as_str() {
+                    "loss" => WorstBy::Loss,
+                    "p95" => WorstBy::Latency,
+                    _ => return Err(format!("Unknown worst host metric: {}", value)),
+                };
+            }
+            "--chart" => self.chart = true,
+            "--width" => {
+                let width: usize = parse_value(&arg, args.next())?;
+
+                if width < 40 {
+                    return Err(format!("{} must be at least 40", arg));
+                }
+
+                self.width = Some(width);
+            }
+            "--compare" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                let mut parts = spec.split(',').map(|host| host.trim().to_string());
+
+                self.compare = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(a), Some(b), None) if !a.is_empty() && !b.is_empty() => Some((a, b)),
+                    _ => return Err(format!("Invalid value for {}: {}", arg, spec)),
+                };
+            }
+            "--readings" => self.readings = Some(parse_value(&arg, args.next())?),
+            "--mtr-style" => self.mtr_style = true,
+            "--screen-file" => self.screen_file = Some(parse_value(&arg, args.next())?),
+            "--dim-after" => {
+                let mins: u64 = parse_value(&arg, args.next())?;
+                self.dim_after = if mins == 0 { None } else { Some(mins) };
+            }
+            "--flap-settle" => self.flap_settle = parse_value(&arg, args.next())?,
+            "--startup-grace" => self.startup_grace = parse_value(&arg, args.next())?,
+            "--outage-bands" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                self.outage_bands = parse_bands(&spec)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, spec))?;
+            }
+            "--stats" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.stats = match value.as_str() {
+                    "compact" => StatsLevel::Compact,
+                    "normal" => StatsLevel::Normal,
+                    "verbose" => StatsLevel::Verbose,
+                    _ => return Err(format!("Unknown stats level: {}", value)),
+                };
+            }
+            "--service" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                self.services.push(Service::parse(&spec)?);
+            }
+            "--hosts" => {
+                let list: String = parse_value(&arg, args.next())?;
+                let hosts: Vec<String> = list.split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty())
+                    .map(|host| host.to_string())
+                    .collect();
+
+                if hosts.is_empty() {
+                    return Err(format!("{} needs at least one host", arg));
+                }
+
+                // The first list replaces the defaults; later ones add to it.
+                if !self.hosts_given {
+                    self.hosts.clear();
+                    self.hosts_given = true;
+                }
+
+                for host in hosts {
+                    if !self.hosts.contains(&host) {
+                        self.hosts.push(host);
+                    }
+                }
+            }
+            "--down-when" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.down_when = match value.as_str() {
+                    "any" => DownWhen::Any,
+                    "majority" => DownWhen::Majority,
+                    "all" => DownWhen::All,
+                    _ => return Err(format!("Unknown down policy: {}", value)),
+                };
+            }
+            "--count-local" => self.count_local = true,
+            "--skip-invalid-hosts" => self.skip_invalid_hosts = true,
+            "--host-sla" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                let (host, sla) = parse_host_sla(&spec)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, spec))?;
+
+                self.host_slas.insert(host, sla);
+            }
+            "--redact" => self.redact = true,
+            "--pin" => self.pinned.push(parse_value(&arg, args.next())?),
+            "--dual-stack" => self.dual_stack.push(parse_value(&arg, args.next())?),
+            "--hosts-file" => self.hosts_file = Some(parse_value(&arg, args.next())?),
+            "--subnet" => {
+                let cidr: String = parse_value(&arg, args.next())?;
+                self.subnet_hosts.extend(subnet::expand(&cidr)?);
+                self.grid = true;
+            }
+            "--alert-style" => {
+                let value: String = parse_value(&arg, args.next())?;
+
+                self.alerts.default = match value.as_str() {
+                    "bell" | "flash" | "both" | "none" => value.parse().unwrap(),
+                    _ => return Err(format!("Unknown alert style: {}", value)),
+                };
+            }
+            "--alert" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                let (event, style) = alert::parse(&spec)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, spec))?;
+
+                self.alerts.insert(event, style);
+            }
+            "--beeps" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                let (event, count) = alert::parse_beeps(&spec)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, spec))?;
+
+                self.alerts.set_beeps(event, count);
+            }
+            "--beep-gap" => {
+                let ms: u64 = parse_value(&arg, args.next())?;
+                self.alerts.beep_gap = Duration::from_millis(ms);
+            }
+            "--jitter" => self.jitter = parse_value(&arg, args.next())?,
+            "--interval" => {
+                let secs: u64 = parse_value(&arg, args.next())?;
+
+                if secs == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+
+                // Slept for in ms, with the jitter added on as an i64.
+                if secs.checked_mul(1000).map_or(true, |ms| ms > i64::MAX as u64) {
+                    return Err(format!("{} is too large: {}s", arg, secs));
+                }
+
+                self.interval = secs;
+            }
+            "--timeout" => {
+                let secs: f64 = parse_value(&arg, args.next())?;
+
+                if !secs.is_finite() || secs <= 0.0 {
+                    return Err(format!("{} must be more than 0, got {}", arg, secs));
+                }
+
+                self.timeout = secs;
+            }
+            "--duration" => self.duration = Some(parse_value(&arg, args.next())?),
+            "--exit-chart" => self.exit_chart = true,
+            "--min-uptime" => {
+                let percentage: f64 = parse_value(&arg, args.next())?;
+
+                if !(0.0..=100.0).contains(&percentage) {
+                    return Err(format!("{} must be in [0, 100], got {}", arg, percentage));
+                }
+
+                self.min_uptime = Some(percentage);
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            title: hostname().unwrap_or_else(|| "uptime".to_string()),
+            renderer: RendererKind::Log,
+            hosts: DEFAULT_HOSTS.iter().map(|h| h.to_string()).collect(),
+            hosts_file: None,
+            interval: POLL_INTERVAL,
+            timeout: PING_TIMEOUT,
+            dual_stack: Vec::new(),
+            services: Vec::new(),
+            host_slas: HashMap::new(),
+            redact: false,
+            pinned: Vec::new(),
+            down_when: DownWhen::All,
+            skip_invalid_hosts: false,
+            count_local: false,
+            ewma_alpha: 0.2,
+            history_size: RAW_CAPACITY,
+            max_mem: None,
+            latency_precision: 2,
+            uptime_precision: 2,
+            floor_uptime: false,
+            time_format: "%Y-%m-%d %H:%M:%S".to_string(),
+            latency_down_threshold: None,
+            latency_slo: None,
+            on_down: None,
+            incident_report: None,
+            incident_template: None,
+            on_up: None,
+            burst_on_fail: 0,
+            self_check: false,
+            status_char: false,
+            bench: None,
+            compare_sessions: None,
+            version: false,
+            capabilities: false,
+            alerts: Alerts::new(),
+            stream_json: false,
+            influx: None,
+            daemon: false,
+            pid_file: None,
+            ics_out: None,
+            status_socket: None,
+            log_mode: LogMode::Polls,
+            histogram: false,
+            outage_bands: OUTAGE_BANDS,
+            flap_settle: 0,
+            startup_grace: 0,
+            stats: StatsLevel::Normal,
+            recent_window: 5 * 60,
+            worst_by: WorstBy::Loss,
+            window: None,
+            health_half_life: None,
+            dim_after: None,
+            grid: false,
+            width: None,
+            chart: false,
+            compare: None,
+            readings: None,
+            mtr_style: false,
+            screen_file: None,
+            jitter: 0,
+            duration: None,
+            min_uptime: None,
+            exit_chart: false,
+            args: Vec::new(),
+            subnet_hosts: Vec::new(),
+            hosts_given: false,
+        }
+    }
+}
+
+fn parse_value<T: ::std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", arg))?;
+
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+/// Reads a config file as a list of arguments. Each line holds one option
+/// followed by its value, if any, which runs to the end of the line. Blank
+/// lines and lines starting with `#` are ignored.
+fn read_config_file(path: &str) -> Result<Vec<String>, String> {
+    let mut contents = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("Could not read config file {}: {}", path, e))?;
+
+    let mut args = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let option = parts.next().unwrap();
+
+        if option == "--config" {
+            return Err(format!("Config file {} cannot include another config file", path));
+        }
+
+        args.push(option.to_string());
+
+        if let Some(value) = parts.next() {
+            args.push(value.trim().to_string());
+        }
+    }
+
+    Ok(args)
+}
+
+/// This machine's hostname, on systems that expose it through procfs.
+fn hostname() -> Option<String> {
+    let mut name = String::new();
+
+    File::open("/proc/sys/kernel/hostname")
+        .and_then(|mut file| file.read_to_string(&mut name))
+        .ok()?;
+
+    let name = name.trim();
+
+    if name.is_empty() { None } else { Some(name.to_string()) }
+}
+
+/// Parses `HOST=PERCENT`, e.g. `8.8.8.8=99.9`.
+fn parse_host_sla(spec: &str) -> Option<(String, f64)> {
+    let mut parts = spec.rsplitn(2, '=');
+    let sla: f64 = parts.next()?.parse().ok()?;
+    let host = parts.next()?;
+
+    if host.is_empty() || !(0.0..=100.0).contains(&sla) {
+        return None;
+    }
+
+    Some((host.to_string(), sla))
+}
+
+/// Parses three increasing, non-zero second counts such as `10,60,300`.
+fn parse_bands(spec: &str) -> Option<[u64; 3]> {
+    let bands: Vec<u64> = spec.split(',')
+        .map(|b| b.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    if bands.len() != 3 || bands[0] == 0 || bands[0] >= bands[1] || bands[1] >= bands[2] {
+        return None;
+    }
+
+    Some([bands[0], bands[1], bands[2]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(args: &str) -> Config {
+        Config::layer(args.split_whitespace().map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn host_lists_concatenate() {
+        let config = layer("--hosts 1.1.1.1,9.9.9.9")
+            .merge(layer("--hosts 8.8.8.8 --pin 1.1.1.1"))
+            .merge(layer("--hosts 1.1.1.1 --pin 8.8.8.8"))
+            .finish()
+            .unwrap();
+
+        assert_eq!(config.hosts, ["1.1.1.1", "9.9.9.9", "8.8.8.8"]);
+        assert_eq!(config.pinned, ["1.1.1.1", "8.8.8.8"]);
+    }
+
+    #[test]
+    fn layers_without_hosts_keep_the_defaults() {
+        let config = layer("--title shared").merge(layer("--histogram")).finish().unwrap();
+
+        assert_eq!(config.hosts, DEFAULT_HOSTS);
+    }
+
+    #[test]
+    fn scalar_options_override() {
+        let config = layer("--title shared --latency-precision 3 --flap-settle 5")
+            .merge(layer("--title laptop --latency-precision 2"))
+            .finish()
+            .unwrap();
+
+        assert_eq!(config.title, "laptop");
+        assert_eq!(config.latency_precision, 2);
+        assert_eq!(config.flap_settle, 5);
+    }
+
+    fn sources(file: &str, environment: &[(&str, &str)], cli: &str) -> Result<Config, String> {
+        let split = |args: &str| args.split_whitespace().map(str::to_string).collect();
+        let environment: HashMap<String, String> = environment.iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Config::from_sources(vec![("test.conf".to_string(), split(file))],
+            |name| environment.get(name).cloned(), split(cli))
+    }
+
+    #[test]
+    fn environment_overrides_files() {
+        let config = sources("--interval 5 --timeout 3 --title file",
+            &[("UPTIME_INTERVAL", "10"), ("UPTIME_TITLE", "env")], "").unwrap();
+
+        assert_eq!(config.interval, 10);
+        assert_eq!(config.timeout, 3.0);
+        assert_eq!(config.title, "env");
+    }
+
+    #[test]
+    fn command_line_overrides_environment() {
+        let config = sources("--interval 5",
+            &[("UPTIME_INTERVAL", "10"), ("UPTIME_TIMEOUT", "4")], "--interval 30").unwrap();
+
+        assert_eq!(config.interval, 30);
+        assert_eq!(config.timeout, 4.0);
+    }
+
+    #[test]
+    fn hosts_accumulate_across_sources() {
+        let config = sources("--hosts 1.1.1.1", &[("UPTIME_HOSTS", "8.8.8.8,9.9.9.9")],
+            "--hosts 4.2.2.2").unwrap();
+
+        assert_eq!(config.hosts, ["1.1.1.1", "8.8.8.8", "9.9.9.9", "4.2.2.2"]);
+    }
+
+    #[test]
+    fn bad_environment_values_name_the_variable() {
+        let error = sources("", &[("UPTIME_TIMEOUT", "soon")], "").err().unwrap();
+
+        assert!(error.starts_with("In UPTIME_TIMEOUT:"), "{}", error);
+    }
+
+    #[test]
+    fn every_listed_output_has_an_option() {
+        for &(name, option) in OUTPUTS.iter() {
+            let unknown = format!("Unknown argument: {}", option);
+            let parsed = Config::layer(vec![option.to_string(), "out".to_string()]);
+
+            assert!(parsed.err().map_or(true, |e| e != unknown), "{} is not an option", name);
+        }
+    }
+
+    #[test]
+    fn options_are_checked_against_other_layers() {
+        assert!(Config::layer(vec!["--min-uptime".to_string(), "99".to_string()]).is_ok());
+        assert!(layer("--min-uptime 99").finish().is_err());
+        assert!(layer("--min-uptime 99").merge(layer("--duration 60")).finish().is_ok());
+    }
+
+    #[test]
+    fn max_mem_that_overflows_is_an_error() {
+        let huge = (usize::MAX / 1024).to_string();
+
+        assert_eq!(layer("--max-mem 2").max_mem, Some(2 * 1024 * 1024));
+        assert!(Config::layer(vec!["--max-mem".to_string(), huge]).is_err());
+    }
+
+    #[test]
+    fn interval_that_overflows_in_ms_is_an_error() {
+        let huge = (u64::MAX / 1000).to_string();
+
+        assert_eq!(layer("--interval 5").interval, 5);
+        assert!(Config::layer(vec!["--interval".to_string(), huge]).is_err());
+    }
+
+    #[test]
+    fn streams_write_every_poll_by_default() {
+        assert_eq!(layer("--stream-json").finish().unwrap().log_mode, LogMode::Polls);
+    }
+}
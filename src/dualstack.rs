@@ -0,0 +1,53 @@
+use std::net::{IpAddr, ToSocketAddrs};
+
+/// The IPv4 and IPv6 addresses a hostname resolves to, if any.
+pub struct DualStack {
+    pub host: String,
+    pub v4: Option<IpAddr>,
+    pub v6: Option<IpAddr>,
+}
+
+impl DualStack {
+    pub fn resolve(host: &str) -> DualStack {
+        let addrs: Vec<IpAddr> = (host, 0).to_socket_addrs()
+            .map(|addrs| addrs.map(|a| a.ip()).collect())
+            .unwrap_or_default();
+
+        DualStack {
+            host: host.to_string(),
+            v4: addrs.iter().cloned().find(|a| a.is_ipv4()),
+            v6: addrs.iter().cloned().find(|a| a.is_ipv6()),
+        }
+    }
+
+    /// Probe targets paired with their display labels.
+    pub fn targets(&self) -> Vec<(String, String)> {
+        let mut targets = Vec::new();
+
+        if let Some(v4) = self.v4 {
+            targets.push((v4.to_string(), format!("{} (v4)", self.host)));
+        }
+
+        if let Some(v6) = self.v6 {
+            targets.push((v6.to_string(), format!("{} (v6)", self.host)));
+        }
+
+        targets
+    }
+
+    /// Notes for the families that did not resolve, calling the host
+    /// `name`.
+    pub fn missing(&self, name: &str) -> Vec<String> {
+        let mut missing = Vec::new();
+
+        if self.v4.is_none() {
+            missing.push(format!("{} (v4): no A record", name));
+        }
+
+        if self.v6.is_none() {
+            missing.push(format!("{} (v6): no AAAA record", name));
+        }
+
+        missing
+    }
+}
@@ -0,0 +1,61 @@
+use oping::PingError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Failures that stop the monitor from starting or from completing a poll.
+#[derive(Debug)]
+pub enum MonitorError {
+    /// The command line could not be parsed.
+    Config(String),
+    /// The hosts file could not be read.
+    HostsFile(io::Error),
+    /// There is nothing to monitor.
+    NoHosts,
+    /// Some configured hosts are malformed; one message per host.
+    InvalidHosts(Vec<String>),
+    /// A recorded session could not be loaded for comparison.
+    Session(String),
+    /// The ping library failed to set up or send a poll.
+    Ping(PingError),
+}
+
+impl fmt::Display for MonitorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MonitorError::Config(ref e) => write!(f, "{}", e),
+            MonitorError::HostsFile(ref e) => write!(f, "Could not read hosts file: {}", e),
+            MonitorError::NoHosts => write!(f, "Hosts file does not list any hosts."),
+            MonitorError::InvalidHosts(ref problems) => {
+                write!(f, "Invalid hosts:")?;
+
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+
+                write!(f, "\nFix them, or pass --skip-invalid-hosts to monitor the rest.")
+            }
+            MonitorError::Session(ref e) => write!(f, "Could not load session {}", e),
+            MonitorError::Ping(ref e) => write!(f, "Ping failed: {}", e),
+        }
+    }
+}
+
+impl Error for MonitorError {
+    fn description(&self) -> &str {
+        match *self {
+            MonitorError::Config(_) => "invalid arguments",
+            MonitorError::HostsFile(_) => "could not read hosts file",
+            MonitorError::NoHosts => "no hosts",
+            MonitorError::InvalidHosts(_) => "invalid hosts",
+            MonitorError::Session(_) => "could not load session",
+            MonitorError::Ping(_) => "ping failed",
+        }
+    }
+}
+
+impl From<PingError> for MonitorError {
+    fn from(e: PingError) -> MonitorError {
+        MonitorError::Ping(e)
+    }
+}
@@ -0,0 +1,567 @@
+use clock::{Clock, SystemClock};
+use config::WorstBy;
+use samples::{Rollup, Samples};
+use slo;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use time::Timespec;
+use tracker::as_secs_f64;
+
+/// Number of recent successful samples used to judge the latency trend.
+const TREND_WINDOW: usize = 5;
+
+/// How long a changed address stays highlighted.
+const ADDRESS_CHANGE_HIGHLIGHT: u64 = 60;
+
+/// Slope, in ms per second, below which latency is considered steady.
+const TREND_DEADBAND: f64 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+impl Trend {
+    pub fn arrow(&self) -> char {
+        match *self {
+            Trend::Rising => '↑',
+            Trend::Falling => '↓',
+            Trend::Steady => '→',
+        }
+    }
+}
+
+/// How dropped polls are spread out over time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LossPattern {
+    /// Drops cluster into runs longer than independent loss would give.
+    Bursty,
+    Uniform,
+}
+
+impl LossPattern {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LossPattern::Bursty => "bursty",
+            LossPattern::Uniform => "uniform",
+        }
+    }
+}
+
+/// Classifies a sequence of polls, `true` meaning dropped. With independent
+/// loss at rate `p`, runs of drops average `1 / (1 - p)` polls; runs that
+/// average half again as long count as bursty. Needs at least two drops.
+pub fn loss_pattern(lost: &[bool]) -> Option<LossPattern> {
+    let drops = lost.iter().filter(|&&l| l).count();
+
+    if drops < 2 || drops == lost.len() {
+        return None;
+    }
+
+    let runs = (0..lost.len())
+        .filter(|&i| lost[i] && (i == 0 || !lost[i - 1]))
+        .count();
+
+    let rate = drops as f64 / lost.len() as f64;
+    let mean_run = drops as f64 / runs as f64;
+
+    if mean_run > 1.5 / (1.0 - rate) {
+        Some(LossPattern::Bursty)
+    }
+    else {
+        Some(LossPattern::Uniform)
+    }
+}
+
+/// Up/down bookkeeping for a single host, driven by its own dropped flag.
+pub struct HostRecord {
+    pub up: Duration,
+    pub down: Duration,
+    pub dropped: bool,
+    /// Exponentially-weighted moving average of the latency in ms, seeded
+    /// with the first successful sample.
+    pub latency: Option<f64>,
+    /// When the host last answered, if it has since startup.
+    pub last_success: Option<Timespec>,
+    samples: Samples,
+    /// Address the hostname last resolved to.
+    pub address: Option<String>,
+    /// Previous address and when it changed.
+    address_change: Option<(String, Instant)>,
+    last: Instant,
+}
+
+impl HostRecord {
+    fn new(now: Instant, dropped: bool, capacity: usize) -> HostRecord {
+        HostRecord {
+            up: Duration::from_secs(0),
+            down: Duration::from_secs(0),
+            dropped,
+            latency: None,
+            last_success: None,
+            samples: Samples::new(now, capacity),
+            address: None,
+            address_change: None,
+            last: now,
+        }
+    }
+
+    fn update(&mut self, now: Instant, dropped: bool) {
+        let elapsed = now.duration_since(self.last);
+
+        if self.dropped {
+            self.down += elapsed;
+        }
+        else {
+            self.up += elapsed;
+        }
+
+        self.dropped = dropped;
+        self.last = now;
+    }
+
+    fn add_sample(&mut self, now: Instant, wall: Timespec, latency_ms: Option<f64>, alpha: f64) {
+        if let Some(sample) = latency_ms {
+            self.latency = Some(match self.latency {
+                Some(avg) => alpha * sample + (1.0 - alpha) * avg,
+                None => sample,
+            });
+
+            self.last_success = Some(wall);
+        }
+
+        self.samples.push(now, latency_ms);
+    }
+
+    /// Polls from the last hour, oldest first, with `None` for drops.
+    pub fn raw_samples(&self) -> &VecDeque<(Instant, Option<f64>)> {
+        self.samples.raw()
+    }
+
+    /// Polls from the last `span`, oldest first.
+    fn recent(&self, now: Instant, span: Duration) -> Vec<(Instant, Option<f64>)> {
+        self.samples.raw().iter()
+            .filter(|&&(at, _)| now.duration_since(at) < span)
+            .cloned()
+            .collect()
+    }
+
+    /// Share of the polls in the last `span` that were dropped, as a
+    /// percentage, and how those drops were spread out.
+    pub fn loss(&self, now: Instant, span: Duration) -> (f64, Option<LossPattern>) {
+        let lost: Vec<bool> = self.recent(now, span).iter().map(|&(_, l)| l.is_none()).collect();
+
+        if lost.is_empty() {
+            return (0.0, None);
+        }
+
+        let drops = lost.iter().filter(|&&l| l).count();
+
+        (drops as f64 / lost.len() as f64 * 100.0, loss_pattern(&lost))
+    }
+
+    /// Latencies of the successful polls in the last `span`.
+    fn recent_latencies(&self, now: Instant, span: Duration) -> Vec<f64> {
+        self.recent(now, span).into_iter()
+            .filter_map(|(_, latency)| latency)
+            .collect()
+    }
+
+    /// Direction of the least-squares slope, against time, over the most
+    /// recent successful samples in the last `span`.
+    pub fn trend(&self, now: Instant, span: Duration) -> Trend {
+        let recent: Vec<(Instant, f64)> = self.recent(now, span).into_iter()
+            .rev()
+            .filter_map(|(at, latency)| latency.map(|l| (at, l)))
+            .take(TREND_WINDOW)
+            .collect();
+
+        if recent.len() < 2 {
+            return Trend::Steady;
+        }
+
+        let origin = recent[recent.len() - 1].0;
+        let points: Vec<(f64, f64)> = recent.iter()
+            .map(|&(at, l)| (as_secs_f64(at.duration_since(origin)), l))
+            .collect();
+
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+
+        let (mut num, mut den) = (0.0, 0.0);
+
+        for &(x, y) in &points {
+            let dx = x - mean_x;
+            num += dx * (y - mean_y);
+            den += dx * dx;
+        }
+
+        if den == 0.0 {
+            return Trend::Steady;
+        }
+
+        let slope = num / den;
+
+        if slope > TREND_DEADBAND {
+            Trend::Rising
+        }
+        else if slope < -TREND_DEADBAND {
+            Trend::Falling
+        }
+        else {
+            Trend::Steady
+        }
+    }
+
+    /// The previous address if the host's address changed recently.
+    pub fn recent_address_change(&self, now: Instant) -> Option<&str> {
+        match self.address_change {
+            Some((ref old, at)) if now.duration_since(at).as_secs() < ADDRESS_CHANGE_HIGHLIGHT => {
+                Some(old)
+            }
+            _ => None,
+        }
+    }
+
+    /// Percentage of the monitored time this host has been responding,
+    /// including the period that is still in progress.
+    pub fn uptime_percentage(&self, now: Instant) -> f64 {
+        let open = now.duration_since(self.last);
+        let (up, down) = if self.dropped {
+            (self.up, self.down + open)
+        }
+        else {
+            (self.up + open, self.down)
+        };
+
+        let total = as_secs_f64(up) + as_secs_f64(down);
+
+        if total == 0.0 {
+            return if self.dropped { 0.0 } else { 100.0 };
+        }
+
+        as_secs_f64(up) / total * 100.0
+    }
+}
+
+/// Fewest raw polls per host that `trim` will keep, so that the recent
+/// statistics still have a minute or so to work with.
+const MIN_CAPACITY: usize = 60;
+
+/// Per-host history, keyed by hostname.
+pub struct HostHistory<C: Clock = SystemClock> {
+    clock: C,
+    hosts: HashMap<String, HostRecord>,
+    ewma_alpha: f64,
+    /// Raw polls kept per host.
+    capacity: usize,
+}
+
+impl<C: Clock> HostHistory<C> {
+    pub fn new(clock: C, ewma_alpha: f64, capacity: usize) -> HostHistory<C> {
+        HostHistory {
+            clock,
+            hosts: HashMap::new(),
+            ewma_alpha,
+            capacity,
+        }
+    }
+
+    /// Records a poll result for `host`. Returns true if the host changed
+    /// between responding and dropped since the previous poll.
+    pub fn update(&mut self, host: &str, dropped: bool, latency_ms: f64) -> bool {
+        let (now, wall) = (self.clock.now(), self.clock.wall());
+
+        let changed = match self.hosts.get_mut(host) {
+            Some(record) => {
+                let changed = record.dropped != dropped;
+                record.update(now, dropped);
+                changed
+            }
+            None => {
+                self.hosts.insert(host.to_string(), HostRecord::new(now, dropped, self.capacity));
+                false
+            }
+        };
+
+        let alpha = self.ewma_alpha;
+        let sample = if dropped { None } else { Some(latency_ms) };
+        self.hosts.get_mut(host).unwrap().add_sample(now, wall, sample, alpha);
+
+        changed
+    }
+
+    pub fn host_latency(&self, host: &str) -> Option<f64> {
+        self.hosts.get(host).and_then(|record| record.latency)
+    }
+
+    /// Latency trend of `host` over the last `span`.
+    pub fn host_trend(&self, host: &str, span: Duration) -> Trend {
+        let now = self.clock.now();
+        self.hosts.get(host).map_or(Trend::Steady, |record| record.trend(now, span))
+    }
+
+    /// Loss of `host` over the last `span`, as `HostRecord::loss`.
+    pub fn host_loss(&self, host: &str, span: Duration) -> Option<(f64, Option<LossPattern>)> {
+        let now = self.clock.now();
+        self.hosts.get(host).map(|record| record.loss(now, span))
+    }
+
+    /// Records the address `host` resolved to, returning the previous one
+    /// if it changed.
+    pub fn set_address(&mut self, host: &str, address: &str) -> Option<String> {
+        let record = self.hosts.get_mut(host)?;
+
+        if record.address.as_ref().is_some_and(|a| a == address) {
+            return None;
+        }
+
+        let old = record.address.replace(address.to_string());
+
+        if let Some(ref old) = old {
+            record.address_change = Some((old.clone(), self.clock.now()));
+        }
+
+        old
+    }
+
+    pub fn remove(&mut self, host: &str) {
+        self.hosts.remove(host);
+    }
+
+    /// Rough number of bytes held by every host's samples.
+    pub fn memory(&self) -> usize {
+        self.hosts.values().map(|record| record.samples.memory()).sum()
+    }
+
+    /// Cuts history until `memory` fits in `limit` bytes: first by halving
+    /// the raw polls kept per host, down to `MIN_CAPACITY`, then by
+    /// dropping the oldest rollups.
+    pub fn trim(&mut self, limit: usize) {
+        while self.memory() > limit && self.capacity > MIN_CAPACITY {
+            let now = self.clock.now();
+            self.capacity = (self.capacity / 2).max(MIN_CAPACITY);
+
+            for record in self.hosts.values_mut() {
+                record.samples.set_capacity(self.capacity, now);
+            }
+        }
+
+        while self.memory() > limit {
+            let mut dropped = false;
+
+            for record in self.hosts.values_mut() {
+                dropped |= record.samples.drop_oldest();
+            }
+
+            if !dropped {
+                break;
+            }
+        }
+    }
+
+    /// Raw polls currently kept per host.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Summary of `host`'s polls over the last `span`.
+    pub fn host_summary(&self, host: &str, span: Duration) -> Option<Rollup> {
+        let now = self.clock.now();
+        self.hosts.get(host).map(|record| record.samples.summary(now, span))
+    }
+
+    /// Summary of every host's polls over the last `span`, combined.
+    pub fn summary(&self, span: Duration) -> Option<Rollup> {
+        let now = self.clock.now();
+
+        self.hosts.values().fold(None, |acc, record| {
+            let summary = record.samples.summary(now, span);
+
+            Some(match acc {
+                Some(mut acc) => {
+                    acc.merge(&summary);
+                    acc
+                }
+                None => summary,
+            })
+        })
+    }
+
+    /// Latencies of every successful poll, across all hosts, in the last
+    /// `span`.
+    pub fn latencies(&self, span: Duration) -> Vec<f64> {
+        let now = self.clock.now();
+
+        self.hosts.values()
+            .flat_map(|record| record.recent_latencies(now, span))
+            .collect()
+    }
+
+    /// The host doing worst over the last `span` by `metric`, with its loss
+    /// percentage or 95th percentile latency. Ties go to the host that
+    /// sorts first. Hosts with no loss never count as worst by loss.
+    pub fn worst(&self, metric: WorstBy, span: Duration) -> Option<(&str, f64)> {
+        let now = self.clock.now();
+        let mut worst: Option<(&str, f64)> = None;
+
+        for host in self.hosts() {
+            let record = &self.hosts[host];
+
+            let value = match metric {
+                WorstBy::Loss => {
+                    let summary = record.samples.summary(now, span);
+
+                    if summary.dropped == 0 {
+                        continue;
+                    }
+
+                    summary.dropped as f64 / summary.polls as f64 * 100.0
+                }
+                WorstBy::Latency => {
+                    match slo::percentile(&mut record.recent_latencies(now, span), 95.0) {
+                        Some(p95) => p95,
+                        None => continue,
+                    }
+                }
+            };
+
+            if worst.map_or(true, |(_, highest)| value > highest) {
+                worst = Some((host, value));
+            }
+        }
+
+        worst
+    }
+
+    /// The previous address of `host` if it changed recently.
+    pub fn host_address_change(&self, host: &str) -> Option<&str> {
+        let now = self.clock.now();
+        self.hosts.get(host).and_then(|record| record.recent_address_change(now))
+    }
+
+    pub fn get(&self, host: &str) -> Option<&HostRecord> {
+        self.hosts.get(host)
+    }
+
+    pub fn host_uptime_percentage(&self, host: &str) -> Option<f64> {
+        let now = self.clock.now();
+        self.hosts.get(host).map(|record| record.uptime_percentage(now))
+    }
+
+    /// Hostnames in a stable order for display.
+    pub fn hosts(&self) -> Vec<&str> {
+        let mut hosts: Vec<&str> = self.hosts.keys().map(|h| h.as_str()).collect();
+        hosts.sort();
+        hosts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use std::rc::Rc;
+
+    #[test]
+    fn ewma_converges_on_constant_input() {
+        let mut history = HostHistory::new(SystemClock, 0.2, 60);
+
+        for _ in 0..10 {
+            history.update("a", false, 20.0);
+        }
+
+        assert_eq!(history.host_latency("a"), Some(20.0));
+
+        let mut previous = 20.0;
+
+        for _ in 0..30 {
+            history.update("a", false, 50.0);
+
+            let latency = history.host_latency("a").unwrap();
+            assert!(latency > previous && latency <= 50.0);
+            previous = latency;
+        }
+
+        assert!(50.0 - previous < 30.0 * 0.8f64.powi(30) + 1e-9);
+    }
+
+    #[test]
+    fn loss_pattern_needs_two_drops_and_a_success() {
+        assert_eq!(loss_pattern(&[]), None);
+        assert_eq!(loss_pattern(&[false, true, false, false]), None);
+        assert_eq!(loss_pattern(&[true, true, true]), None);
+    }
+
+    #[test]
+    fn scattered_drops_are_uniform() {
+        let mut lost = vec![false; 100];
+
+        for i in (0..100).step_by(10) {
+            lost[i] = true;
+        }
+
+        assert_eq!(loss_pattern(&lost), Some(LossPattern::Uniform));
+    }
+
+    #[test]
+    fn clustered_drops_are_bursty() {
+        let mut lost = vec![false; 100];
+
+        for drop in lost.iter_mut().skip(40).take(10) {
+            *drop = true;
+        }
+
+        assert_eq!(loss_pattern(&lost), Some(LossPattern::Bursty));
+    }
+
+    #[test]
+    fn ewma_ignores_drops() {
+        let mut history = HostHistory::new(SystemClock, 0.2, 60);
+
+        history.update("a", true, 0.0);
+        assert_eq!(history.host_latency("a"), None);
+
+        history.update("a", false, 30.0);
+        history.update("a", true, 0.0);
+        assert_eq!(history.host_latency("a"), Some(30.0));
+    }
+
+    #[test]
+    fn loss_and_trend_only_see_the_window() {
+        let start = Instant::now();
+        let mut record = HostRecord::new(start, false, 120);
+
+        // Latency climbs for half a minute, then every poll drops.
+        for i in 0..60 {
+            let sample = if i < 30 { Some(10.0 + i as f64 * 3.0) } else { None };
+            let (now, wall) = (start + Duration::from_secs(i), Timespec::new(i as i64, 0));
+            record.add_sample(now, wall, sample, 0.2);
+        }
+
+        let now = start + Duration::from_secs(60);
+        let (hour, half_minute) = (Duration::from_secs(60 * 60), Duration::from_secs(30));
+
+        assert_eq!(record.loss(now, hour), (50.0, Some(LossPattern::Bursty)));
+        assert_eq!(record.loss(now, half_minute), (100.0, None));
+        assert_eq!(record.trend(now, hour), Trend::Rising);
+        assert_eq!(record.trend(now, half_minute), Trend::Steady);
+    }
+
+    #[test]
+    fn timestamps_come_from_the_clock() {
+        let clock = Rc::new(MockClock::new());
+        let mut history = HostHistory::new(clock.clone(), 0.2, 60);
+
+        history.update("a", false, 10.0);
+        clock.advance(Duration::from_secs(3));
+        history.update("a", true, 0.0);
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(history.host_uptime_percentage("a"), Some(75.0));
+        assert_eq!(history.get("a").unwrap().last_success, Some(Timespec::new(0, 0)));
+    }
+}
@@ -0,0 +1,28 @@
+use std::process::Command;
+use std::thread;
+
+/// Runs `command` through the shell on a detached thread so a slow script
+/// never holds up polling. The exit status is written to the log.
+pub fn run(command: &str, env: Vec<(&'static str, String)>) {
+    let command = command.to_string();
+
+    thread::spawn(move || {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .envs(env)
+            .status();
+
+        match status {
+            Ok(ref status) if status.success() => {
+                debug!("Hook `{}` exited with {}", command, status);
+            }
+            Ok(status) => {
+                warn!("Hook `{}` exited with {}", command, status);
+            }
+            Err(e) => {
+                error!("Failed to run hook `{}`: {}", command, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,51 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// A file listing one host per line, re-read whenever it is modified.
+/// Blank lines and anything after a `#` are ignored.
+pub struct HostsFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl HostsFile {
+    pub fn new(path: &str) -> HostsFile {
+        HostsFile {
+            path: PathBuf::from(path),
+            modified: None,
+        }
+    }
+
+    pub fn read(&self) -> io::Result<Vec<String>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut hosts = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let host = line.split('#').next().unwrap().trim();
+
+            if !host.is_empty() && !hosts.iter().any(|h| h == host) {
+                hosts.push(host.to_string());
+            }
+        }
+
+        Ok(hosts)
+    }
+
+    /// Returns the new host list if the file changed since the last call.
+    pub fn reload(&mut self) -> Option<io::Result<Vec<String>>> {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if self.modified == Some(modified) {
+            return None;
+        }
+
+        self.modified = Some(modified);
+        Some(self.read())
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, Write};
+use time::{self, Timespec};
+
+/// Outages collected for export as an iCalendar file, one event each.
+pub struct Calendar {
+    title: String,
+    events: Vec<(Timespec, Timespec, Vec<String>)>,
+}
+
+impl Calendar {
+    pub fn new(title: &str) -> Calendar {
+        Calendar {
+            title: title.to_string(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an outage from `start` to `end` that affected `hosts`.
+    pub fn add(&mut self, start: Timespec, end: Timespec, hosts: &[String]) {
+        self.events.push((start, end, hosts.to_vec()));
+    }
+
+    /// Rewrites the file at `path` with every outage so far, so that it is
+    /// complete and valid whenever it is read.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        File::create(path)?.write_all(self.render().as_bytes())
+    }
+
+    fn render(&self) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//uptime//outages//EN".to_string(),
+            format!("X-WR-CALNAME:{}", escape(&format!("{} outages", self.title))),
+        ];
+
+        let stamp = utc(time::get_time());
+
+        for &(start, end, ref hosts) in &self.events {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:{}.{}@{}", start.sec, start.nsec, escape(&self.title)));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(format!("DTSTART:{}", utc(start)));
+            lines.push(format!("DTEND:{}", utc(end)));
+            lines.push(format!("SUMMARY:{}", escape(&format!("Internet outage ({})", self.title))));
+
+            if !hosts.is_empty() {
+                lines.push(format!("DESCRIPTION:{}",
+                    escape(&format!("Hosts down: {}", hosts.join(", ")))));
+            }
+
+            lines.push("END:VEVENT".to_string());
+        }
+
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+/// Formats `ts` as an iCalendar UTC date-time.
+fn utc(ts: Timespec) -> String {
+    time::strftime("%Y%m%dT%H%M%SZ", &time::at_utc(ts)).unwrap()
+}
+
+/// Escapes `s` for use as a TEXT value.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Ends `line` with CRLF, folding it so that no line is over 75 octets.
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 2);
+    let mut width = 0;
+
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+
+        out.push(c);
+        width += c.len_utf8();
+    }
+
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape("a, b; c\\d\ne"), "a\\, b\\; c\\\\d\\ne");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = "x".repeat(160);
+        let folded = fold(&line);
+
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn writes_one_event_per_outage() {
+        let mut calendar = Calendar::new("home");
+        calendar.add(Timespec::new(0, 0), Timespec::new(90, 0), &["8.8.8.8".to_string()]);
+
+        let ics = calendar.render();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("DTSTART:19700101T000000Z\r\n"));
+        assert!(ics.contains("DTEND:19700101T000130Z\r\n"));
+        assert!(ics.contains("DESCRIPTION:Hosts down: 8.8.8.8\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+    }
+}
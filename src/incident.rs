@@ -0,0 +1,79 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use time::{self, Timespec};
+use format_duration;
+
+/// Used unless `--incident-template` names another. Each `{name}` is
+/// replaced by the matching field.
+pub const DEFAULT_TEMPLATE: &str = "\
+Internet outage
+  Start:    {start}
+  End:      {end}
+  Duration: {duration}
+  Hosts:    {hosts}
+  Peak:     {peak} of {total} hosts down at once
+
+";
+
+/// What was seen during one outage, for the report written when it ends.
+pub struct Incident {
+    start: Timespec,
+    hosts: Vec<String>,
+    peak: usize,
+}
+
+impl Incident {
+    pub fn new(start: Timespec) -> Incident {
+        Incident {
+            start,
+            hosts: Vec::new(),
+            peak: 0,
+        }
+    }
+
+    /// Records the hosts that dropped in one poll.
+    pub fn observe(&mut self, failed: &[String]) {
+        for host in failed {
+            if !self.hosts.contains(host) {
+                self.hosts.push(host.clone());
+            }
+        }
+
+        self.peak = self.peak.max(failed.len());
+    }
+
+    pub fn start(&self) -> Timespec {
+        self.start
+    }
+
+    /// Every host that dropped at some point during the outage.
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    /// Fills in `template` for an outage that ended at `end`.
+    pub fn report(&self, template: &str, time_format: &str, end: Timespec, duration: Duration,
+        total: usize) -> String {
+        let format = |ts: Timespec| time::strftime(time_format, &time::at(ts)).unwrap();
+
+        template
+            .replace("{start}", &format(self.start))
+            .replace("{end}", &format(end))
+            .replace("{duration}", &format_duration(duration))
+            .replace("{hosts}", &self.hosts.join(", "))
+            .replace("{peak}", &self.peak.to_string())
+            .replace("{total}", &total.to_string())
+    }
+}
+
+pub fn read_template(path: &str) -> io::Result<String> {
+    let mut template = String::new();
+    File::open(path)?.read_to_string(&mut template)?;
+    Ok(template)
+}
+
+/// Appends `report` to the file at `path`, creating it if needed.
+pub fn append(path: &str, report: &str) -> io::Result<()> {
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(report.as_bytes())
+}
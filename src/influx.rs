@@ -0,0 +1,196 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often batched points are written.
+const FLUSH_INTERVAL: u64 = 5;
+
+/// Points kept while the server is unreachable; the oldest are dropped
+/// beyond this. Also bounds the queue to the writer, which drops new points
+/// while it is full.
+const MAX_PENDING: usize = 10_000;
+
+/// Seconds to wait for the server to accept a connection.
+const CONNECT_TIMEOUT: u64 = 2;
+
+/// Pushes points in InfluxDB line protocol to a `/write` endpoint over
+/// plain HTTP. Points are batched on a background thread and a failed
+/// write is retried with the next batch.
+pub struct Influx {
+    sender: SyncSender<String>,
+}
+
+impl Influx {
+    /// Starts the writer for a URL such as
+    /// `http://localhost:8086/write?db=uptime`.
+    pub fn start(url: &str) -> Result<Influx, String> {
+        let endpoint = Endpoint::parse(url)?;
+        let (sender, receiver) = mpsc::sync_channel(MAX_PENDING);
+
+        thread::spawn(move || run(endpoint, receiver));
+
+        Ok(Influx { sender })
+    }
+
+    /// Queues a point, dropping it if the writer is too far behind.
+    pub fn send(&self, point: String) {
+        let _ = self.sender.try_send(point);
+    }
+}
+
+/// Formats one poll of `host` as a `ping` point, tagged with the network
+/// it went out on if known. `ts` is in nanoseconds since the epoch.
+pub fn point(host: &str, network: Option<&str>, dropped: bool, latency_ms: f64, ts: i64) -> String {
+    let mut point = format!("ping,host={}", escape(host));
+
+    if let Some(network) = network {
+        point.push_str(&format!(",network={}", escape(network)));
+    }
+
+    point.push_str(&format!(" dropped={}i", if dropped { 1 } else { 0 }));
+
+    if !dropped {
+        point.push_str(&format!(",latency={}", latency_ms));
+    }
+
+    point.push_str(&format!(" {}", ts));
+    point
+}
+
+/// Escapes the characters that are special in tag values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if c == ',' || c == ' ' || c == '=' || c == '\\' {
+            out.push('\\');
+        }
+
+        out.push(c);
+    }
+
+    out
+}
+
+struct Endpoint {
+    /// `host:port` to connect to.
+    address: String,
+    host: String,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Endpoint, String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(format!("InfluxDB URL must start with http://: {}", url)),
+        };
+
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/write"),
+        };
+
+        if host.is_empty() {
+            return Err(format!("InfluxDB URL has no host: {}", url));
+        }
+
+        let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+
+        Ok(Endpoint {
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Connects to the first address the host resolves to that answers
+    /// within `CONNECT_TIMEOUT`.
+    fn connect(&self) -> Result<TcpStream, String> {
+        let addresses = self.address.to_socket_addrs().map_err(|e| e.to_string())?;
+        let mut last_error = format!("{} did not resolve", self.host);
+
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, Duration::from_secs(CONNECT_TIMEOUT)) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// POSTs `body`, returning an error unless the server answers 2xx.
+    fn post(&self, body: &str) -> Result<(), String> {
+        let mut stream = self.connect()?;
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(FLUSH_INTERVAL)));
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(FLUSH_INTERVAL)));
+
+        write!(stream, "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain\r\n\
+            Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path, self.host, body.len(), body).map_err(|e| e.to_string())?;
+
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+
+        let status = response.split_whitespace().nth(1).unwrap_or("");
+
+        if status.starts_with('2') {
+            Ok(())
+        }
+        else {
+            Err(response.lines().next().unwrap_or("no response").to_string())
+        }
+    }
+}
+
+fn run(endpoint: Endpoint, receiver: Receiver<String>) {
+    let interval = Duration::from_secs(FLUSH_INTERVAL);
+    let mut pending: Vec<String> = Vec::new();
+    let mut last_flush = Instant::now();
+    let mut failing = false;
+
+    loop {
+        let wait = interval.checked_sub(last_flush.elapsed()).unwrap_or(Duration::from_secs(0));
+
+        match receiver.recv_timeout(wait) {
+            Ok(point) => pending.push(point),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if last_flush.elapsed() < interval {
+            continue;
+        }
+
+        last_flush = Instant::now();
+
+        if pending.is_empty() {
+            continue;
+        }
+
+        match endpoint.post(&pending.join("\n")) {
+            Ok(()) => {
+                if failing {
+                    info!("Writing to InfluxDB works again.");
+                    failing = false;
+                }
+
+                pending.clear();
+            }
+            Err(e) => {
+                if !failing {
+                    warn!("Could not write to InfluxDB, will retry: {}", e);
+                    failing = true;
+                }
+
+                if pending.len() > MAX_PENDING {
+                    let excess = pending.len() - MAX_PENDING;
+                    pending.drain(..excess);
+                }
+            }
+        }
+    }
+}
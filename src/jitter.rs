@@ -0,0 +1,32 @@
+use time;
+
+/// Random offsets for the poll schedule, so monitors started together do
+/// not stay in lockstep. Offsets are uniform over `-max..=max` ms, which
+/// keeps the average interval unchanged. This only needs to be spread out,
+/// not unpredictable, so a xorshift generator seeded from the clock does.
+pub struct Jitter {
+    state: u64,
+    max_ms: u64,
+}
+
+impl Jitter {
+    pub fn new(max_ms: u64) -> Jitter {
+        Jitter {
+            state: time::precise_time_ns() | 1,
+            max_ms,
+        }
+    }
+
+    /// The next offset, in ms.
+    pub fn next(&mut self) -> i64 {
+        if self.max_ms == 0 {
+            return 0;
+        }
+
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        (self.state % (2 * self.max_ms + 1)) as i64 - self.max_ms as i64
+    }
+}
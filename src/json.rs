@@ -0,0 +1,340 @@
+use std::char;
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::Duration;
+use tracker::{as_secs_f64, State};
+
+/// The outcome of pinging one host during a poll.
+pub struct HostResult {
+    pub host: String,
+    pub dropped: bool,
+    pub latency_ms: f64,
+}
+
+/// Quotes and escapes `s` as a JSON string.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Formats a single poll as one line of JSON. `network` is the SSID or
+/// interface the poll went out on, if known.
+pub fn poll(ts: &::time::Timespec, title: &str, network: Option<&str>, hosts: &[HostResult],
+    state: State) -> String {
+    let mut out = format!("{{\"ts\":{},\"title\":{},\"network\":{},\"hosts\":[",
+        timestamp(ts), string(title), network.map_or("null".to_string(), string));
+
+    for (i, result) in hosts.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(out, "{{\"host\":{},\"dropped\":{},\"latency_ms\":",
+            string(&result.host), result.dropped);
+
+        if result.dropped {
+            out.push_str("null}");
+        }
+        else {
+            let _ = write!(out, "{}}}", result.latency_ms);
+        }
+    }
+
+    let _ = write!(out, "],\"state\":{}}}", string(state.name()));
+    out
+}
+
+/// Formats the current state for the status socket.
+pub fn status(ts: &::time::Timespec, title: &str, state: State, uptime: f64,
+    hosts: &[HostResult]) -> String {
+    let mut out = format!(concat!("{{\"ts\":{},\"title\":{},\"state\":{},",
+        "\"uptime\":{:.4},\"hosts\":["),
+        timestamp(ts), string(title), string(state.name()), uptime);
+
+    for (i, result) in hosts.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(out, "{{\"host\":{},\"dropped\":{}}}",
+            string(&result.host), result.dropped);
+    }
+
+    out.push_str("]}");
+    out
+}
+
+/// Formats a link state change as one line of JSON. `duration` is how long
+/// the `from` state lasted.
+pub fn transition(ts: &::time::Timespec, title: &str, from: State, to: State,
+    duration: Duration) -> String {
+    format!(concat!("{{\"ts\":{},\"title\":{},\"event\":\"transition\",",
+        "\"from\":{},\"to\":{},\"duration_s\":{:.3}}}"),
+        timestamp(ts), string(title), string(from.name()), string(to.name()),
+        as_secs_f64(duration))
+}
+
+/// Describes what this build supports, for scripts that need to check
+/// before relying on a feature.
+pub fn capabilities(version: &str, outputs: &[&str], features: &[&str]) -> String {
+    let list = |items: &[&str]| {
+        items.iter().map(|item| string(item)).collect::<Vec<_>>().join(",")
+    };
+
+    format!(concat!("{{\"version\":{},",
+        "\"renderers\":[\"log\",\"ansi\"],",
+        "\"outputs\":[{}],",
+        "\"features\":[{}]}}"),
+        string(version), list(outputs), list(features))
+}
+
+fn timestamp(ts: &::time::Timespec) -> String {
+    format!("{}.{:03}", ts.sec, ts.nsec / 1_000_000)
+}
+
+/// A parsed JSON value. Object members keep their order.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member `key`, if this is an object that has one.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref members) => {
+                members.iter().find(|&(name, _)| name == key).map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a single JSON document, such as one line written by `poll`.
+pub fn parse(s: &str) -> Result<Value, String> {
+    let mut parser = Parser { chars: s.chars().peekable() };
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("Unexpected {:?} after value", c)),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected {:?}, found {:?}", expected, c)),
+            None => Err(format!("Expected {:?}, found end of input", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek().cloned() {
+            Some('n') => self.literal("null", Value::Null),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected {:?}", c)),
+            None => Err("Unexpected end of input".to_string()),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let mut text = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_digit() && !"+-.eE".contains(c) {
+                break;
+            }
+
+            text.push(c);
+            self.chars.next();
+        }
+
+        text.parse().map(Value::Number).map_err(|_| format!("Invalid number: {}", text))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = self.chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("Invalid escape: \\u{}", hex))?;
+
+                        out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => return Err(format!("Invalid escape: \\{}", c)),
+                    None => break,
+                },
+                Some(c) => out.push(c),
+                None => break,
+            }
+        }
+
+        Err("Unterminated string".to_string())
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                Some(c) => return Err(format!("Expected ',' or ']', found {:?}", c)),
+                None => return Err("Unterminated array".to_string()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(members)),
+                Some(c) => return Err(format!("Expected ',' or '}}', found {:?}", c)),
+                None => return Err("Unterminated object".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_list_what_they_are_given() {
+        let listed = parse(&capabilities("1.0", &["stream-json", "ics"], &[])).unwrap();
+        let names = |key: &str| -> Vec<String> {
+            listed.get(key).and_then(|list| list.as_array()).unwrap().iter()
+                .map(|item| item.as_str().unwrap().to_string())
+                .collect()
+        };
+
+        assert_eq!(listed.get("version").and_then(|v| v.as_str()), Some("1.0"));
+        assert_eq!(names("outputs"), ["stream-json", "ics"]);
+        assert!(names("features").is_empty());
+    }
+}
@@ -3,28 +3,106 @@ extern crate env_logger;
 extern crate oping;
 extern crate time;
 
-use oping::{Ping, PingResult};
+mod alert;
+mod bench;
+mod clock;
+mod config;
+mod dualstack;
+mod error;
+mod history;
+mod hooks;
+mod hostsfile;
+mod ics;
+mod incident;
+mod influx;
+mod jitter;
+mod json;
+mod monitor;
+mod probe;
+mod render;
+mod route;
+mod samples;
+mod service;
+mod session;
+mod signal;
+mod slo;
+mod socket;
+mod source;
+mod subnet;
+mod terminal;
+mod tracker;
+mod validate;
+
+use alert::Event;
+use clock::SystemClock;
+use config::{Config, DownWhen, RendererKind};
+use dualstack::DualStack;
+use error::MonitorError;
+use hostsfile::HostsFile;
+use influx::Influx;
+use jitter::Jitter;
+use monitor::{Monitor, Poll};
+use render::{AnsiRenderer, Renderer};
+use route::RouteWatch;
+use session::Summary;
+use socket::StatusSocket;
+use source::{OpingSource, ResponseSource};
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::time::{Duration, Instant};
-use std::thread;
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::process;
+use std::slice;
+use time::Timespec;
+use tracker::State;
 use log::{LogRecord, LogLevelFilter};
 use env_logger::LogBuilder;
 
+/// Seconds between polls, unless `--interval` says otherwise.
+pub const POLL_INTERVAL: u64 = 1;
+
+/// Seconds after the default route changes during which an outage is put
+/// down to the change.
+const ROUTE_CHANGE_WINDOW: u64 = 10;
+
+/// Marks in the `--exit-chart` timeline.
+const EXIT_CHART_WIDTH: usize = 60;
+
+/// Seconds to wait for each echo reply, unless `--timeout` says otherwise.
+pub const PING_TIMEOUT: f64 = 2.0;
+
+/// Latency, in ms, above which a response is reported as high.
+pub const LATENCY_WARNING: f64 = 100.0;
+
+/// Latencies, in ms, above this are shown as `>999 ms` so that they keep
+/// to the width of the columns. Exports always carry the true value.
+pub const LATENCY_DISPLAY_CAP: f64 = 999.0;
+
 fn main() {
     let format = |record: &LogRecord| {
         let t = time::now();
         format!("{},{:03} - {} - {}",
             time::strftime("%Y-%m-%d %H:%M:%S", &t).unwrap(),
-            t.tm_nsec / 1000_000,
+            t.tm_nsec / 1_000_000,
             record.level(),
             record.args()
         )
     };
 
+    let config = Config::from_args();
+
+    // A daemon has nobody watching, so only problems are worth logging.
+    let level = match config {
+        Ok(ref config) if config.daemon => LogLevelFilter::Warn,
+        _ => LogLevelFilter::Info,
+    };
+
     let mut builder = LogBuilder::new();
     builder
         .format(format)
-        .filter(None, LogLevelFilter::Info);
+        .filter(None, level);
 
     if env::var("RUST_LOG").is_ok() {
        builder.parse(&env::var("RUST_LOG").unwrap());
@@ -32,54 +110,453 @@ fn main() {
 
     builder.init().unwrap();
 
-    info!("Running.");
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => exit_with(MonitorError::Config(e)),
+    };
+
+    if config.version {
+        println!("uptime {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    if config.capabilities {
+        let outputs: Vec<&str> = config::OUTPUTS.iter().map(|&(name, _)| name).collect();
+        println!("{}", json::capabilities(env!("CARGO_PKG_VERSION"), &outputs, &features()));
+        return;
+    }
+
+    if let Some(iterations) = config.bench {
+        bench::run(&config, iterations);
+        return;
+    }
+
+    if let Some((ref a, ref b)) = config.compare_sessions {
+        let loaded = Summary::load(a).and_then(|first| Ok((first, Summary::load(b)?)));
+
+        match loaded {
+            Ok((first, second)) => print!("{}", session::compare((a, &first), (b, &second))),
+            Err(e) => exit_with(MonitorError::Session(e)),
+        }
+
+        return;
+    }
+
+    if config.status_char {
+        process::exit(status_char(&config));
+    }
+
+    info!("Running: {}", config.title);
+
+    if config.self_check {
+        info!("Alerts: down uses {}, up uses {}",
+            config.alerts.get(Event::Down).name(), config.alerts.get(Event::Up).name());
+    }
     
-    let uptime = Instant::now();
-    let mut downtime = None;
+    let mut renderer: Option<Box<dyn Renderer>> = match config.renderer {
+        RendererKind::Log => None,
+        RendererKind::Ansi => Some(Box::new(AnsiRenderer::new(&config))),
+    };
+    let incident_template = match config.incident_template {
+        Some(ref path) => match incident::read_template(path) {
+            Ok(template) => template,
+            Err(e) => exit_with(MonitorError::Config(
+                format!("Could not read incident template {}: {}", path, e))),
+        },
+        None => incident::DEFAULT_TEMPLATE.to_string(),
+    };
+    let influx = match config.influx {
+        Some(ref url) => match Influx::start(url) {
+            Ok(influx) => Some(influx),
+            Err(e) => exit_with(MonitorError::Config(e)),
+        },
+        None => None,
+    };
+    let status_socket = match config.status_socket {
+        Some(ref path) => match StatusSocket::start(path) {
+            Ok(socket) => Some(socket),
+            Err(e) => exit_with(MonitorError::Config(
+                format!("Could not listen on status socket {}: {}", path, e))),
+        },
+        None => None,
+    };
+    let mut last_activity = Instant::now();
+    let mut source = OpingSource::new(config.timeout);
+    let mut jitter = Jitter::new(config.jitter);
+    let mut route = RouteWatch::new();
+    let mut route_changed: Option<Instant> = None;
+    let mut hosts = config.hosts.clone();
+    let mut hosts_file = config.hosts_file.as_ref().map(|path| HostsFile::new(path));
+
+    if let Some(ref mut file) = hosts_file {
+        match file.reload() {
+            Some(Ok(ref list)) if !list.is_empty() => hosts = list.clone(),
+            Some(Err(e)) => exit_with(MonitorError::HostsFile(e)),
+            _ => exit_with(MonitorError::NoHosts),
+        }
+    }
+
+    // Pseudonyms are handed out before anything is logged, so that not
+    // even the startup warnings name a host.
+    let mut labels = HashMap::new();
+
+    if config.redact {
+        redact(&mut labels, &hosts);
+    }
+
+    let problems: Vec<String> = hosts.iter()
+        .filter_map(|host| {
+            validate::problem(host).map(|reason| format!("{}: {}", label(&labels, host), reason))
+        })
+        .collect();
+
+    if !problems.is_empty() {
+        if !config.skip_invalid_hosts || problems.len() == hosts.len() {
+            exit_with(MonitorError::InvalidHosts(problems));
+        }
+
+        for problem in &problems {
+            warn!("Skipping {}", problem);
+        }
+
+        hosts.retain(|host| validate::problem(host).is_none());
+    }
+
+    // A name that does not resolve may just mean the link is already down,
+    // so it is only worth a warning.
+    for host in hosts.iter().filter(|host| host.parse::<IpAddr>().is_err()) {
+        if let Err(e) = validate::resolves(host) {
+            warn!("Could not resolve {}: {}", label(&labels, host), e);
+        }
+    }
+
+    let mut unresolved = Vec::new();
+
+    for host in &config.dual_stack {
+        let resolved = DualStack::resolve(host);
+
+        if config.redact {
+            redact(&mut labels, slice::from_ref(host));
+        }
+
+        for (target, name) in resolved.targets() {
+            if !hosts.contains(&target) {
+                hosts.push(target.clone());
+            }
+
+            // Dual-stack labels name the host, so pseudonyms replace them.
+            if config.redact {
+                redact(&mut labels, &[target]);
+            }
+            else {
+                labels.insert(target, name);
+            }
+        }
+
+        for missing in resolved.missing(label(&labels, host)) {
+            warn!("{}", missing);
+            unresolved.push(missing);
+        }
+    }
+
+    let mut monitor = Monitor::new(&config, SystemClock, hosts, labels);
+    monitor.incident_template = incident_template;
+    monitor.influx = influx;
+
+    signal::install();
+
+    // Written last, so no startup failure can leave a stale one behind.
+    if let Some(ref path) = config.pid_file {
+        let written = File::create(path)
+            .and_then(|mut file| writeln!(file, "{}", process::id()));
+
+        if let Err(e) = written {
+            exit_with(MonitorError::Config(format!("Could not write PID file {}: {}", path, e)));
+        }
+    }
 
     loop {
-        let mut ping = Ping::new();
-        ping.set_timeout(2.0);
-        ping.add_host("8.8.8.8");
-        ping.add_host("4.2.2.2");
-        ping.add_host("208.67.222.222");
-
-        let mut dropped = 0;
-        
-        let responses = ping.send().unwrap();
-
-        for resp in responses {
-            if resp.dropped > 0 {
-                if downtime.is_none() {
-                    debug!("No response from {}", resp.hostname);
+        if let Some(ref mut file) = hosts_file {
+            match file.reload() {
+                Some(Ok(list)) => {
+                    if list.is_empty() {
+                        warn!("Hosts file is empty, keeping the current hosts.");
+                    }
+                    else {
+                        monitor.set_hosts(list);
+                    }
                 }
-                dropped += 1;
+                Some(Err(e)) => warn!("Could not reload hosts file: {}", e),
+                None => {}
+            }
+        }
+
+        if route.changed() {
+            match route.link() {
+                Some(link) => info!("Default route moved to {}", link.name()),
+                None => info!("Default route went away"),
+            }
+
+            route_changed = Some(Instant::now());
+        }
+
+        let network = route.link().map(|link| link.name());
+        let window = Duration::from_secs(ROUTE_CHANGE_WINDOW);
+        let local_change = route_changed.is_some_and(|at| at.elapsed() < window);
+
+        let poll = monitor.poll(&mut source, network, local_change);
+        let tracker = &monitor.tracker;
+        let history = &monitor.history;
+        let hosts = &monitor.hosts;
+        let labels = &monitor.labels;
+
+        if poll.transition.is_some() {
+            last_activity = Instant::now();
+        }
+
+        if let Some(ref mut renderer) = renderer {
+            let idle = config.dim_after
+                .is_some_and(|mins| last_activity.elapsed() >= Duration::from_secs(mins * 60));
+
+            if idle {
+                renderer.draw_dimmed(tracker);
             }
             else {
-                debug!("Response from host {}: latency {} ms",
-                    resp.hostname, resp.latency_ms);
+                renderer.draw_stats(tracker, history, labels);
+                renderer.draw_link(route.link());
+
+                if let Some(secs) = monitor.retry_in() {
+                    renderer.draw_notice(&format!("Pinging is failing, retrying in {}s", secs));
+                }
+
+                renderer.draw_today(&monitor.today);
+
+                if let Some(ref slo) = config.latency_slo {
+                    renderer.draw_slo(slo, monitor.slo_value, &monitor.slo_tracker);
+                }
 
-                if resp.latency_ms > 100.0 {
-                    warn!("High latency from host {}: {} ms", resp.hostname, resp.latency_ms);
+                if let Some(ref pair) = config.compare {
+                    renderer.draw_compare(pair, labels, history);
                 }
+
+                if config.chart {
+                    renderer.draw_chart(hosts, labels, history);
+                }
+                else {
+                    if config.grid {
+                        renderer.draw_grid(hosts, labels, history);
+                    }
+                    else if let Some(count) = config.readings {
+                        renderer.draw_readings(hosts, labels, history, count);
+                    }
+                    else {
+                        renderer.draw_hosts(hosts, labels, &unresolved, history);
+                    }
+
+                    renderer.draw_outages(tracker.downtimes());
+
+                    if config.histogram {
+                        renderer.draw_histogram(tracker.histogram(), &monitor.band_labels);
+                    }
+                }
+            }
+
+            renderer.present();
+        }
+
+        if config.stream_json {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+
+            for line in stream_lines(&config, &time::get_time(), network, &poll, tracker.state()) {
+                let _ = writeln!(stdout, "{}", line);
+            }
+
+            let _ = stdout.flush();
+        }
+
+        if let Some(ref socket) = status_socket {
+            socket.update(json::status(&time::get_time(), &config.title, tracker.state(),
+                tracker.uptime_percentage(), &poll.results));
+        }
+
+        if config.self_check {
+            if let Err(drift) = tracker.self_check(Duration::from_millis(1)) {
+                error!("Self-check failed: up + down time is off from the session by {:?}",
+                    drift);
+            }
+        }
+
+        if config.duration.is_some_and(|secs| tracker.session() >= Duration::from_secs(secs)) {
+            break;
+        }
+
+        match monitor.retry_in() {
+            Some(secs) => signal::sleep(Duration::from_secs(secs)),
+            None => {
+                let interval = ((config.interval * 1000) as i64).saturating_add(jitter.next());
+                signal::sleep(Duration::from_millis(interval as u64));
             }
         }
 
-        if dropped == 3 && downtime.is_none() {
-            error!("All pings failed: Internet is down.");
-            downtime = Some(Instant::now());
+        if signal::stop_requested() {
+            info!("Stopping on request.");
+            break;
+        }
+    }
+
+    let tracker = &monitor.tracker;
+
+    if let Some(ref path) = config.pid_file {
+        let _ = fs::remove_file(path);
+    }
+
+    if let Some(ref path) = config.status_socket {
+        let _ = socket::remove(path);
+    }
+
+    if config.exit_chart {
+        eprintln!("[{}]", tracker.timeline(EXIT_CHART_WIDTH));
+        eprintln!(" # up   ~ degraded   _ down   ({} per mark)",
+            format_duration(tracker.session() / EXIT_CHART_WIDTH as u32));
+    }
+
+    if let Some(threshold) = config.min_uptime {
+        let uptime = tracker.uptime_percentage();
+        let verdict = if uptime >= threshold { "PASS" } else { "FAIL" };
+
+        eprintln!("Uptime: {:.3}% (minimum {}%): {}", uptime, threshold, verdict);
+
+        if uptime < threshold {
+            process::exit(1);
+        }
+    }
+}
+
+/// The `--stream-json` lines for one poll, as many as `--log-mode` asks
+/// for.
+fn stream_lines(config: &Config, ts: &Timespec, network: Option<&str>, poll: &Poll, state: State)
+    -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if config.log_mode.polls() {
+        lines.push(json::poll(ts, &config.title, network, &poll.results, state));
+    }
+
+    if config.log_mode.transitions() {
+        if let Some((ended, duration)) = poll.transition {
+            lines.push(json::transition(ts, &config.title, ended, state, duration));
         }
-        else if downtime.is_some() && dropped != 3 {
-            info!("Internet was down for {}", 
-                format_duration(Instant::now()
-                    .duration_since(downtime.unwrap())));
-            downtime = None;
+    }
+
+    lines
+}
+
+/// Optional behaviour that depends on the platform this was built for.
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+
+    // The default route is read from /proc/net/route.
+    if cfg!(target_os = "linux") {
+        features.push("route-detection");
+    }
+
+    features
+}
+
+/// Logs `e` and exits with the status used for startup failures.
+fn exit_with(e: MonitorError) -> ! {
+    error!("{}", e);
+    process::exit(2);
+}
+
+/// Polls once and prints a one-glyph summary for status bars, returning
+/// the exit code.
+fn status_char(config: &Config) -> i32 {
+    let (responses, rejected) = match OpingSource::new(config.timeout).poll(&config.hosts) {
+        Ok(polled) => polled,
+        Err(e) => {
+            error!("{}", e);
+            return 2;
         }
+    };
+
+    // Hosts liboping would not take count as failed, as in `Monitor::poll`.
+    let failed: Vec<String> = responses.iter()
+        .filter(|resp| resp.dropped)
+        .map(|resp| resp.hostname.clone())
+        .chain(rejected)
+        .collect();
+
+    let slow: Vec<String> = responses.iter()
+        .filter(|resp| !resp.dropped)
+        .filter(|resp| config.latency_down_threshold.is_some_and(|t| resp.latency_ms > t))
+        .map(|resp| resp.hostname.clone())
+        .collect();
+
+    let state = monitor::link_state(config, &config.hosts, &failed, &slow);
+
+    let glyph = match state {
+        State::Down => "○",
+        State::Degraded => "◐",
+        State::Up => "●",
+    };
+
+    let percentage = if config.hosts.is_empty() {
+        0.0
+    }
+    else {
+        (config.hosts.len() - failed.len()) as f64 / config.hosts.len() as f64 * 100.0
+    };
 
-        thread::sleep(Duration::from_secs(1));
+    println!("{} {:.0}%", glyph, percentage);
+
+    if state == State::Down { 1 } else { 0 }
+}
+
+/// Whether `failed` out of `total` hosts dropping takes the link down.
+fn should_mark_down(policy: DownWhen, failed: usize, total: usize) -> bool {
+    match policy {
+        DownWhen::Any => failed > 0,
+        DownWhen::Majority => failed * 2 > total,
+        DownWhen::All => failed == total,
+    }
+}
+
+/// Whether `host` is a loopback address or `localhost`.
+pub fn is_local(host: &str) -> bool {
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Display name for `host`, falling back to the address itself.
+fn label<'a>(labels: &'a HashMap<String, String>, host: &'a str) -> &'a str {
+    labels.get(host).map_or(host, |l| l.as_str())
+}
+
+/// `hosts` by their labels.
+fn labelled(labels: &HashMap<String, String>, hosts: &[String]) -> Vec<String> {
+    hosts.iter().map(|host| label(labels, host).to_string()).collect()
+}
+
+/// Gives each host in `hosts` that has no label yet the next pseudonym,
+/// so a host keeps its name for the whole session.
+fn redact(labels: &mut HashMap<String, String>, hosts: &[String]) {
+    for host in hosts {
+        if !labels.contains_key(host) {
+            let pseudonym = format!("host-{}", labels.len() + 1);
+            labels.insert(host.clone(), pseudonym);
+        }
     }
 }
 
+fn hook_env(hosts: &[String], outage: Duration) -> Vec<(&'static str, String)> {
+    vec![
+        ("OUTAGE_DURATION", format_duration(outage)),
+        ("HOSTS", hosts.join(",")),
+        ("TIMESTAMP", time::strftime("%Y-%m-%d %H:%M:%S", &time::now()).unwrap()),
+    ]
+}
+
 fn format_duration(dur: Duration) -> String {
     let mut total = dur.as_secs();
 
@@ -90,4 +567,123 @@ fn format_duration(dur: Duration) -> String {
     let secs = total;
 
     format!("{:02}:{:02}:{:02}", hours, mins, secs)
-}
\ No newline at end of file
+}
+
+/// Formats an uptime percentage to `precision` decimals. With `floor`, it
+/// is rounded down instead, so any downtime at all shows below 100%.
+fn format_percentage(pct: f64, precision: usize, floor: bool) -> String {
+    if floor {
+        let scale = 10f64.powi(precision as i32);
+        format!("{:.*}%", precision, (pct * scale).floor() / scale)
+    }
+    else {
+        format!("{:.*}%", precision, pct)
+    }
+}
+
+/// Formats a latency for display, switching to microseconds below 1 ms
+/// and capping at `LATENCY_DISPLAY_CAP`.
+fn format_latency(ms: f64, precision: usize) -> String {
+    if ms > LATENCY_DISPLAY_CAP {
+        format!(">{:.0} ms", LATENCY_DISPLAY_CAP)
+    }
+    else if ms < 1.0 {
+        format!("{:.0} µs", ms * 1000.0)
+    }
+    else {
+        format!("{:.*} ms", precision, ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use json::HostResult;
+    use std::rc::Rc;
+    use tracker::TimeTracker;
+
+    #[test]
+    fn sub_millisecond_latency_is_in_microseconds() {
+        assert_eq!(format_latency(0.85, 2), "850 µs");
+        assert_eq!(format_latency(0.0004, 2), "0 µs");
+    }
+
+    #[test]
+    fn typical_latency_uses_the_precision() {
+        assert_eq!(format_latency(23.456, 2), "23.46 ms");
+        assert_eq!(format_latency(23.456, 0), "23 ms");
+        assert_eq!(format_latency(1.0, 1), "1.0 ms");
+    }
+
+    #[test]
+    fn high_latency_keeps_its_value() {
+        assert_eq!(format_latency(650.25, 2), "650.25 ms");
+        assert_eq!(format_latency(999.0, 0), "999 ms");
+    }
+
+    #[test]
+    fn pathological_latency_is_capped() {
+        assert_eq!(format_latency(999.5, 2), ">999 ms");
+        assert_eq!(format_latency(10000.0, 2), ">999 ms");
+    }
+
+    #[test]
+    fn floored_uptime_shows_a_tiny_outage() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+
+        tracker.set_state(State::Down);
+        clock.advance(Duration::from_secs(1));
+        tracker.set_state(State::Up);
+        clock.advance(Duration::from_secs(99_999));
+        tracker.set_state(State::Up);
+
+        let uptime = tracker.uptime_percentage();
+        assert_eq!(format_percentage(uptime, 2, false), "100.00%");
+        assert_eq!(format_percentage(uptime, 2, true), "99.99%");
+        assert_eq!(format_percentage(100.0, 2, true), "100.00%");
+    }
+
+    fn events(mode: &str, transition: Option<(State, Duration)>) -> Vec<String> {
+        let config = Config::layer(vec!["--log-mode".to_string(), mode.to_string()])
+            .unwrap()
+            .finish()
+            .unwrap();
+        let dropped = HostResult { host: "1.1.1.1".to_string(), dropped: true, latency_ms: 0.0 };
+        let poll = Poll { results: vec![dropped], transition };
+
+        stream_lines(&config, &Timespec::new(0, 0), None, &poll, State::Down).iter()
+            .map(|line| {
+                let value = json::parse(line).unwrap();
+                value.get("event").and_then(|event| event.as_str()).unwrap_or("poll").to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn log_modes_pick_the_stream_lines() {
+        let down = Some((State::Up, Duration::from_secs(30)));
+
+        assert_eq!(events("transitions", None), Vec::<String>::new());
+        assert_eq!(events("transitions", down), ["transition"]);
+        assert_eq!(events("polls", None), ["poll"]);
+        assert_eq!(events("polls", down), ["poll"]);
+        assert_eq!(events("both", None), ["poll"]);
+        assert_eq!(events("both", down), ["poll", "transition"]);
+    }
+
+    #[test]
+    fn down_policies_need_different_shares_of_hosts() {
+        // (failed, total) for polls where none, one, under half, half, most
+        // and all of the hosts dropped.
+        let polls = [(0, 4), (1, 4), (1, 3), (2, 4), (3, 4), (4, 4)];
+        let marked = |policy| -> Vec<bool> {
+            polls.iter().map(|&(failed, total)| should_mark_down(policy, failed, total)).collect()
+        };
+
+        assert_eq!(marked(DownWhen::Any), [false, true, true, true, true, true]);
+        assert_eq!(marked(DownWhen::Majority), [false, false, false, false, true, true]);
+        assert_eq!(marked(DownWhen::All), [false, false, false, false, false, true]);
+    }
+}
@@ -0,0 +1,886 @@
+use alert::{self, Event};
+use clock::{Clock, SystemClock};
+use config::{Config, Window};
+use history::HostHistory;
+use hooks;
+use ics::Calendar;
+use incident::{self, Incident};
+use influx::{self, Influx};
+use json::HostResult;
+use probe;
+use slo;
+use source::ResponseSource;
+use std::collections::HashMap;
+use std::time::Duration;
+use time::{self, Timespec};
+use tracker::{self, State, TimeTracker};
+use {format_duration, format_latency, format_percentage, hook_env, is_local, label, labelled,
+    redact, should_mark_down, LATENCY_WARNING};
+
+/// How often to remind that an outage is still in progress.
+const OUTAGE_REMINDER: u64 = 60;
+
+/// Longest wait, in seconds, between retries while pinging keeps failing.
+const MAX_BACKOFF: u64 = 60;
+
+/// What one poll saw.
+pub struct Poll {
+    /// Each host's outcome, under its label.
+    pub results: Vec<HostResult>,
+    /// The state that ended and how long it lasted, if the link changed.
+    pub transition: Option<(State, Duration)>,
+}
+
+/// Everything carried from one poll to the next, and the work each poll
+/// does before anything is drawn or written out.
+pub struct Monitor<'a, C: Clock + Clone = SystemClock> {
+    config: &'a Config,
+    clock: C,
+    pub tracker: TimeTracker<C>,
+    /// Restarts at each local midnight, for the daily summary.
+    pub today: TimeTracker<C>,
+    /// One per configured service, in the same order.
+    pub services: Vec<TimeTracker<C>>,
+    pub slo_tracker: TimeTracker<C>,
+    /// The latency the SLO was last measured at.
+    pub slo_value: Option<f64>,
+    pub history: HostHistory<C>,
+    pub hosts: Vec<String>,
+    pub labels: HashMap<String, String>,
+    pub band_labels: [String; 4],
+    /// Polls in a row that could not be sent at all.
+    ping_failures: u32,
+    first_poll: bool,
+    /// Reminders already logged for the outage in progress.
+    reminded: u64,
+    incident: Option<Incident>,
+    pub incident_template: String,
+    calendar: Calendar,
+    pub influx: Option<Influx>,
+}
+
+impl<'a, C: Clock + Clone> Monitor<'a, C> {
+    pub fn new(config: &'a Config, clock: C, hosts: Vec<String>, labels: HashMap<String, String>)
+        -> Monitor<'a, C> {
+        let mut tracker = TimeTracker::new(clock.clone());
+        tracker.set_bands(config.outage_bands);
+        tracker.set_settle(Duration::from_secs(config.flap_settle));
+        tracker.set_grace(Duration::from_secs(config.startup_grace));
+        if let Some(secs) = config.health_half_life {
+            tracker.set_half_life(Duration::from_secs(secs));
+        }
+        // The session uptime is already in the header, so a session window
+        // leaves the recent row as it was.
+        match config.window {
+            Some(window) if window != Window::Session => tracker.set_recent_window(window.span()),
+            _ => tracker.set_recent_window(Duration::from_secs(config.recent_window)),
+        }
+        let mut today = TimeTracker::new(clock.clone());
+        today.set_bands(config.outage_bands);
+        today.set_settle(Duration::from_secs(config.flap_settle));
+
+        Monitor {
+            config,
+            tracker,
+            today,
+            services: config.services.iter().map(|_| TimeTracker::new(clock.clone())).collect(),
+            slo_tracker: TimeTracker::new(clock.clone()),
+            slo_value: None,
+            history: HostHistory::new(clock.clone(), config.ewma_alpha, config.history_size),
+            hosts,
+            labels,
+            band_labels: tracker::band_labels(&config.outage_bands),
+            ping_failures: 0,
+            first_poll: true,
+            reminded: 0,
+            incident: None,
+            incident_template: incident::DEFAULT_TEMPLATE.to_string(),
+            calendar: Calendar::new(&config.title),
+            influx: None,
+            clock,
+        }
+    }
+
+    /// Switches to monitoring `hosts`, forgetting any that are gone.
+    pub fn set_hosts(&mut self, hosts: Vec<String>) {
+        if self.config.redact {
+            redact(&mut self.labels, &hosts);
+        }
+
+        for host in self.hosts.iter().filter(|h| !hosts.contains(h)) {
+            info!("No longer monitoring {}", label(&self.labels, host));
+            self.history.remove(host);
+        }
+
+        for host in hosts.iter().filter(|h| !self.hosts.contains(h)) {
+            info!("Now monitoring {}", label(&self.labels, host));
+        }
+
+        self.hosts = hosts;
+    }
+
+    /// Seconds to wait before the next poll while pinging is failing.
+    pub fn retry_in(&self) -> Option<u64> {
+        match self.ping_failures {
+            0 => None,
+            failures => Some(backoff(self.config.interval, failures)),
+        }
+    }
+
+    /// Pings every host through `source` once and updates the link state.
+    /// `network` is what the poll went out on, if known, and
+    /// `local_change` whether the default route moved recently.
+    pub fn poll(&mut self, source: &mut dyn ResponseSource, network: Option<&str>,
+        local_change: bool) -> Poll {
+        let config = self.config;
+        let percent = |pct| format_percentage(pct, config.uptime_precision, config.floor_uptime);
+        let trend_span = config.window.map_or(Duration::from_secs(60 * 60), |window| window.span());
+        let mut failed = Vec::new();
+        let mut slow = Vec::new();
+        let mut results = Vec::new();
+
+        let (responses, rejected) = match source.poll(&self.hosts) {
+            Ok(result) => {
+                if self.ping_failures > 0 {
+                    info!("Pinging works again.");
+                    self.ping_failures = 0;
+                }
+
+                result
+            }
+            Err(e) => {
+                self.ping_failures += 1;
+
+                if self.ping_failures == 1 {
+                    error!("{}: counting every host as dropped until it works again.", e);
+                }
+                else {
+                    warn!("{}: retrying in {}s", e, backoff(config.interval, self.ping_failures));
+                }
+
+                for host in &self.hosts {
+                    self.history.update(host, true, 0.0);
+                    failed.push(host.clone());
+                }
+
+                (Vec::new(), Vec::new())
+            }
+        };
+
+        // Nothing is known about the link until the first poll is back, so
+        // the wait for it must not count as uptime.
+        if self.first_poll {
+            self.tracker.reset();
+            self.today.reset();
+            self.slo_tracker.reset();
+
+            for service in &mut self.services {
+                service.reset();
+            }
+
+            self.first_poll = false;
+        }
+
+        if let Some(limit) = config.max_mem {
+            let capacity = self.history.capacity();
+            self.history.trim(limit);
+
+            if self.history.capacity() != capacity {
+                info!("History trimmed to fit --max-mem, now keeping {} raw polls per host",
+                    self.history.capacity());
+            }
+        }
+
+        for host in &rejected {
+            if !self.history.get(host).is_some_and(|record| record.dropped) {
+                warn!("Could not ping {}: not a valid or resolvable host",
+                    label(&self.labels, host));
+            }
+
+            self.history.update(host, true, 0.0);
+            failed.push(host.clone());
+        }
+
+        let now = self.clock.wall();
+        let ts = now.sec * 1_000_000_000 + now.nsec as i64;
+
+        let mut host_went_down = false;
+
+        for resp in responses {
+            let name = label(&self.labels, &resp.hostname);
+
+            if let Some(ref influx) = self.influx {
+                influx.send(influx::point(name, network, resp.dropped, resp.latency_ms, ts));
+            }
+
+            results.push(HostResult {
+                host: name.to_string(),
+                dropped: resp.dropped,
+                latency_ms: resp.latency_ms,
+            });
+
+            if self.history.update(&resp.hostname, resp.dropped, resp.latency_ms) {
+                info!("Host {} is now {}: uptime {}",
+                    name,
+                    if resp.dropped { "down" } else { "up" },
+                    percent(self.history.host_uptime_percentage(&resp.hostname).unwrap()));
+
+                host_went_down |= resp.dropped;
+
+                if resp.dropped && config.burst_on_fail > 0 {
+                    probe::spawn_burst(&resp.hostname, name, config.burst_on_fail,
+                        config.timeout);
+                }
+            }
+
+            if !resp.address.is_empty() {
+                if let Some(old) = self.history.set_address(&resp.hostname, &resp.address) {
+                    if config.redact {
+                        warn!("IP changed for {}", name);
+                    }
+                    else {
+                        warn!("IP changed for {}: {} → {}", name, old, resp.address);
+                    }
+                }
+            }
+
+            if resp.dropped {
+                if !self.tracker.is_down() {
+                    debug!("No response from {}", name);
+                }
+                failed.push(resp.hostname.clone());
+            }
+            else {
+                debug!("Response from host {}: latency {} {} (raw {})",
+                    name,
+                    format_latency(self.history.host_latency(&resp.hostname).unwrap(),
+                        config.latency_precision),
+                    self.history.host_trend(&resp.hostname, trend_span).arrow(),
+                    format_latency(resp.latency_ms, config.latency_precision));
+
+                if resp.latency_ms > LATENCY_WARNING {
+                    warn!("High latency from host {}: {}", name,
+                        format_latency(resp.latency_ms, config.latency_precision));
+                }
+
+                if config.latency_down_threshold.is_some_and(|t| resp.latency_ms > t) {
+                    slow.push(resp.hostname.clone());
+                }
+            }
+        }
+
+        for (service, state) in config.services.iter().zip(self.services.iter_mut()) {
+            let down = service.is_down(&failed);
+
+            if down && !state.is_down() {
+                error!("Service {} is down.", service.name);
+                state.down();
+            }
+            else if !down && state.is_down() {
+                // Not inside `info!`, which skips its arguments when the
+                // level is filtered out.
+                let outage = state.up().unwrap();
+
+                info!("Service {} was down for {}: uptime {}",
+                    service.name,
+                    format_duration(outage),
+                    percent(state.uptime_percentage()));
+            }
+        }
+
+        if let Some(ref slo) = config.latency_slo {
+            self.slo_value = slo::percentile(&mut self.history.latencies(slo.window),
+                slo.percentile);
+
+            let met = self.slo_value.map_or(true, |value| slo.is_met(value));
+
+            if !met && !self.slo_tracker.is_down() {
+                warn!("Latency SLO violated: p{} is {}", slo.percentile,
+                    format_latency(self.slo_value.unwrap(), config.latency_precision));
+                self.slo_tracker.down();
+            }
+            else if met && self.slo_tracker.is_down() {
+                let violation = self.slo_tracker.up().unwrap();
+
+                info!("Latency SLO was violated for {}: met {} of the session",
+                    format_duration(violation),
+                    percent(self.slo_tracker.uptime_percentage()));
+            }
+        }
+
+        // Flagged first, so an outage closed by this poll is flagged too.
+        if local_change {
+            self.tracker.note_local_change();
+        }
+
+        let settling = self.tracker.settling().is_some();
+        let transition = self.tracker.set_state(link_state(config, &self.hosts, &failed, &slow));
+        // Failures during the startup grace period leave the tracker up.
+        let state = self.tracker.state();
+        // Going down again before the settle time is up continues the
+        // outage that is still open rather than starting another.
+        let relapsed = settling && self.tracker.settled().is_none();
+
+        // A full outage gets its own alert below.
+        if host_went_down && state != State::Down {
+            alert::fire(&config.alerts, Event::HostDown);
+        }
+
+        if state == State::Down {
+            if let Some(ref mut incident) = self.incident {
+                incident.observe(&labelled(&self.labels, &failed));
+            }
+        }
+
+        if self.today.is_new_day() {
+            info!("Day over: uptime {}, {} outages, down for {}",
+                percent(self.today.uptime_percentage()),
+                self.today.downtimes().len() + if self.today.is_down() { 1 } else { 0 },
+                format_duration(self.today.total_downtime()));
+
+            self.today.reset();
+        }
+
+        self.today.set_state(state);
+
+        if let Some((ended, duration)) = transition {
+            match ended {
+                State::Down if self.tracker.settled().is_none() => {
+                    info!("Link is back after {}, waiting {} before closing the outage",
+                        format_duration(duration),
+                        format_duration(Duration::from_secs(config.flap_settle)));
+                }
+                State::Degraded => {
+                    info!("Link was degraded for {}: degraded {} of the session",
+                        format_duration(duration),
+                        percent(self.tracker.percentage(State::Degraded)));
+                }
+                State::Down | State::Up => {}
+            }
+        }
+
+        if let Some(outage) = self.tracker.settled() {
+            let end = outage.start + time::Duration::from_std(outage.duration).unwrap();
+            self.outage_ended(outage.duration, end, outage.local_change);
+        }
+
+        if transition.is_some() {
+            match state {
+                State::Down if relapsed => {
+                    warn!("Link is down again: counting it as part of the same outage.");
+                }
+                State::Down => self.outage_started(&failed),
+                State::Degraded => {
+                    warn!("Every host is slow or dropped: the link is degraded.");
+                }
+                State::Up => {}
+            }
+        }
+
+        if let Some(duration) = self.tracker.downtime() {
+            let reminders = duration.as_secs() / OUTAGE_REMINDER;
+
+            if reminders > self.reminded {
+                warn!("Outage in progress: {}", format_duration(duration));
+                self.reminded = reminders;
+            }
+        }
+
+        Poll {
+            results,
+            transition,
+        }
+    }
+
+    /// The hosts as hooks are told about them, by pseudonym with
+    /// `--redact`.
+    fn hook_hosts(&self) -> Vec<String> {
+        if self.config.redact {
+            labelled(&self.labels, &self.hosts)
+        }
+        else {
+            self.hosts.clone()
+        }
+    }
+
+    fn outage_started(&mut self, failed: &[String]) {
+        let config = self.config;
+
+        if config.incident_report.is_some() || config.ics_out.is_some() {
+            let mut started = Incident::new(self.clock.wall());
+            started.observe(&labelled(&self.labels, failed));
+            self.incident = Some(started);
+        }
+
+        if self.services.is_empty() {
+            error!("All pings failed: Internet is down.");
+        }
+        else {
+            error!("Service outage: marking the link as down.");
+        }
+
+        self.reminded = 0;
+        alert::fire(&config.alerts, Event::Down);
+
+        if let Some(ref command) = config.on_down {
+            hooks::run(command, hook_env(&self.hook_hosts(), Duration::from_secs(0)));
+        }
+    }
+
+    /// Reports an outage once the tracker has recorded it. `end` is when
+    /// the link came back for good.
+    fn outage_ended(&mut self, duration: Duration, end: Timespec, local_change: bool) {
+        let config = self.config;
+        let percent = |pct| format_percentage(pct, config.uptime_precision, config.floor_uptime);
+
+        alert::fire(&config.alerts, Event::Up);
+
+        let cause = if local_change { " (local network change)" } else { "" };
+
+        info!("Internet was down for {}{}: uptime {}",
+            format_duration(duration), cause, percent(self.tracker.uptime_percentage()));
+
+        if let Some(incident) = self.incident.take() {
+
+            if let Some(ref path) = config.incident_report {
+                let report = incident.report(&self.incident_template, &config.time_format,
+                    end, duration, self.hosts.len());
+
+                if let Err(e) = incident::append(path, &report) {
+                    warn!("Could not write incident report to {}: {}", path, e);
+                }
+            }
+
+            if let Some(ref path) = config.ics_out {
+                self.calendar.add(incident.start(), end, incident.hosts());
+
+                if let Err(e) = self.calendar.write(path) {
+                    warn!("Could not write calendar {}: {}", path, e);
+                }
+            }
+        }
+
+        if let Some(ref command) = config.on_up {
+            hooks::run(command, hook_env(&self.hook_hosts(), duration));
+        }
+
+        let window = config.window.unwrap_or(Window::Hour);
+
+        for host in self.history.hosts() {
+            let summary = self.history.host_summary(host, window.span()).unwrap();
+
+            match summary.avg() {
+                Some(avg) => info!("  {}: uptime {}, {} {}/{}/{} min/avg/max, {} of {} polls dropped",
+                    label(&self.labels, host),
+                    percent(self.history.host_uptime_percentage(host).unwrap()),
+                    window.label(),
+                    format_latency(summary.min, config.latency_precision),
+                    format_latency(avg, config.latency_precision),
+                    format_latency(summary.max, config.latency_precision),
+                    summary.dropped, summary.polls),
+                None => info!("  {}: uptime {}, no responses in the {}",
+                    label(&self.labels, host),
+                    percent(self.history.host_uptime_percentage(host).unwrap()),
+                    window.label()),
+            }
+        }
+
+        if config.histogram {
+            let buckets: Vec<String> = self.band_labels.iter()
+                .zip(self.tracker.histogram().iter())
+                .map(|(label, count)| format!("{}: {}", label, count))
+                .collect();
+
+            info!("Outage durations: {}", buckets.join(", "));
+        }
+    }
+}
+
+/// The link state implied by the hosts that dropped and the hosts that
+/// answered too slowly, out of `hosts`.
+pub fn link_state(config: &Config, hosts: &[String], failed: &[String], slow: &[String])
+    -> State {
+    // Loopback targets say nothing about the network, so they only count
+    // towards the link state when asked to or when they are all there is.
+    let all_local = hosts.iter().all(|host| is_local(host));
+    let counts = |host: &&String| config.count_local || all_local || !is_local(host);
+    let counted = hosts.iter().filter(&counts).count();
+    let counted_failed = failed.iter().filter(&counts).count();
+    let counted_slow = slow.iter().filter(&counts).count();
+
+    let link_down = if config.services.is_empty() {
+        should_mark_down(config.down_when, counted_failed, counted)
+    }
+    else {
+        config.services.iter().any(|service| service.is_down(failed))
+    };
+
+    if link_down {
+        State::Down
+    }
+    else if config.services.is_empty() && counted_slow > 0
+        && should_mark_down(config.down_when, counted_failed + counted_slow, counted) {
+        State::Degraded
+    }
+    else {
+        State::Up
+    }
+}
+
+/// Seconds to wait after the `failures`th failed poll in a row: the poll
+/// `interval`, doubling with each further failure up to `MAX_BACKOFF`, or
+/// the interval itself if that is longer.
+fn backoff(interval: u64, failures: u32) -> u64 {
+    let exponent = failures.saturating_sub(1).min(16);
+    interval.saturating_mul(1 << exponent).min(MAX_BACKOFF.max(interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use source::FakeSource;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::rc::Rc;
+
+    fn options(args: &str) -> Config {
+        Config::layer(args.split_whitespace().map(str::to_string)).unwrap().finish().unwrap()
+    }
+
+    fn hosts(n: usize) -> Vec<String> {
+        (1..n + 1).map(|i| format!("10.0.0.{}", i)).collect()
+    }
+
+    fn monitor<'a>(config: &'a Config, clock: &Rc<MockClock>, hosts: Vec<String>)
+        -> Monitor<'a, Rc<MockClock>> {
+        Monitor::new(config, clock.clone(), hosts, HashMap::new())
+    }
+
+    /// Polls `source` `polls` times, a second apart as the monitor would,
+    /// returning the states that ended.
+    fn run(monitor: &mut Monitor<Rc<MockClock>>, clock: &MockClock, source: &mut FakeSource,
+        polls: usize) -> Vec<State> {
+        let mut ended = Vec::new();
+
+        for _ in 0..polls {
+            if let Some((state, _)) = monitor.poll(source, None, false).transition {
+                ended.push(state);
+            }
+
+            clock.advance(Duration::from_secs(1));
+        }
+
+        ended
+    }
+
+    fn script(up: usize, down: usize, hosts: usize) -> Vec<Vec<Option<f64>>> {
+        let mut polls = vec![vec![Some(10.0); hosts]; up];
+        polls.extend(vec![vec![None; hosts]; down]);
+        polls
+    }
+
+    #[test]
+    fn steady_link_stays_up() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(3));
+
+        run(&mut monitor, &clock, &mut FakeSource::new(script(10, 0, 3)), 10);
+
+        assert_eq!(monitor.tracker.state(), State::Up);
+        assert!(monitor.tracker.downtimes().is_empty());
+        assert_eq!(monitor.tracker.uptime_percentage(), 100.0);
+    }
+
+    #[test]
+    fn outage_is_recorded_on_recovery() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(2));
+        let mut polls = script(3, 5, 2);
+        polls.extend(script(3, 0, 2));
+
+        let ended = run(&mut monitor, &clock, &mut FakeSource::new(polls), 11);
+
+        assert_eq!(ended, [State::Up, State::Down]);
+        assert_eq!(monitor.tracker.downtimes().len(), 1);
+        assert_eq!(monitor.tracker.downtimes()[0].duration, Duration::from_secs(5));
+        assert_eq!(monitor.tracker.total_downtime(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn outage_in_progress_is_not_yet_recorded() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(2));
+
+        run(&mut monitor, &clock, &mut FakeSource::new(script(2, 4, 2)), 6);
+
+        assert!(monitor.tracker.is_down());
+        assert!(monitor.tracker.downtimes().is_empty());
+        assert_eq!(monitor.tracker.downtime(), Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn partial_failure_depends_on_policy() {
+        let partial = vec![vec![None, None, Some(10.0)]; 4];
+
+        for &(policy, expected) in &[("all", State::Up), ("majority", State::Down)] {
+            let config = options(&format!("--down-when {}", policy));
+            let clock = Rc::new(MockClock::new());
+            let mut monitor = monitor(&config, &clock, hosts(3));
+
+            run(&mut monitor, &clock, &mut FakeSource::new(partial.clone()), 4);
+
+            assert_eq!(monitor.tracker.state(), expected, "--down-when {}", policy);
+        }
+    }
+
+    #[test]
+    fn rejected_hosts_count_as_failed() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let hosts = hosts(2);
+        let mut source = FakeSource::new(vec![vec![None, Some(10.0)]; 3]);
+        source.rejected.push(hosts[1].clone());
+        let mut monitor = monitor(&config, &clock, hosts);
+
+        run(&mut monitor, &clock, &mut source, 3);
+
+        assert!(monitor.tracker.is_down());
+    }
+
+    #[test]
+    fn slow_hosts_degrade_the_link() {
+        let (config, clock) = (options("--latency-down-threshold 200"), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(2));
+        let mut polls = vec![vec![Some(10.0), Some(10.0)]; 2];
+        polls.extend(vec![vec![Some(250.0), None]; 3]);
+        polls.extend(vec![vec![Some(250.0), Some(10.0)]; 2]);
+
+        let ended = run(&mut monitor, &clock, &mut FakeSource::new(polls), 7);
+
+        assert_eq!(ended, [State::Up, State::Degraded]);
+        assert_eq!(monitor.tracker.degradations().len(), 1);
+        assert_eq!(monitor.tracker.degradations()[0].duration, Duration::from_secs(3));
+        assert!(monitor.tracker.downtimes().is_empty());
+    }
+
+    #[test]
+    fn loopback_hosts_do_not_decide_the_link() {
+        let hosts = vec!["127.0.0.1".to_string(), "10.0.0.1".to_string()];
+        let polls = vec![vec![Some(0.1), None]; 3];
+
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut excluded = monitor(&config, &clock, hosts.clone());
+        run(&mut excluded, &clock, &mut FakeSource::new(polls.clone()), 3);
+        assert!(excluded.tracker.is_down());
+
+        let (config, clock) = (options("--count-local"), Rc::new(MockClock::new()));
+        let mut counted = monitor(&config, &clock, hosts);
+        run(&mut counted, &clock, &mut FakeSource::new(polls), 3);
+        assert_eq!(counted.tracker.state(), State::Up);
+    }
+
+    #[test]
+    fn services_decide_the_link() {
+        let config = options("--service web=10.0.0.1,10.0.0.2 --service dns=10.0.0.3");
+        let clock = Rc::new(MockClock::new());
+        let mut monitor = monitor(&config, &clock, hosts(3));
+        let mut polls = vec![vec![None, Some(10.0), None]; 2];
+        polls.extend(vec![vec![None, None, Some(10.0)]; 2]);
+        polls.extend(vec![vec![Some(10.0); 3]; 2]);
+        let mut source = FakeSource::new(polls);
+
+        run(&mut monitor, &clock, &mut source, 2);
+        assert!(!monitor.services[0].is_down());
+        assert!(monitor.services[1].is_down());
+        assert!(monitor.tracker.is_down());
+
+        run(&mut monitor, &clock, &mut source, 2);
+        assert!(monitor.services[0].is_down());
+        assert!(!monitor.services[1].is_down());
+        assert!(monitor.tracker.is_down());
+
+        run(&mut monitor, &clock, &mut source, 2);
+        assert_eq!(monitor.tracker.state(), State::Up);
+        assert_eq!(monitor.tracker.downtimes().len(), 1);
+        assert_eq!(monitor.tracker.downtimes()[0].duration, Duration::from_secs(4));
+    }
+
+    #[test]
+    fn failures_during_the_grace_period_are_ignored() {
+        let (config, clock) = (options("--startup-grace 3"), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(1));
+        let mut source = FakeSource::new(script(0, 5, 1));
+
+        run(&mut monitor, &clock, &mut source, 3);
+        assert_eq!(monitor.tracker.state(), State::Up);
+
+        run(&mut monitor, &clock, &mut source, 2);
+        assert!(monitor.tracker.is_down());
+        assert_eq!(monitor.tracker.downtime(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn flaps_within_settle_time_are_one_outage() {
+        let (config, clock) = (options("--flap-settle 5"), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(1));
+        let mut polls = script(2, 3, 1);
+        polls.extend(script(2, 3, 1));
+        polls.extend(script(10, 0, 1));
+
+        run(&mut monitor, &clock, &mut FakeSource::new(polls), 20);
+
+        assert_eq!(monitor.tracker.downtimes().len(), 1);
+
+        let outage = &monitor.tracker.downtimes()[0];
+        assert_eq!(outage.flaps, 1);
+        assert_eq!(outage.good, Duration::from_secs(2));
+        assert_eq!(outage.duration, Duration::from_secs(8));
+    }
+
+    #[test]
+    fn flaps_are_reported_once_the_outage_settles() {
+        let path = env::temp_dir().join(format!("uptime-test-{}-flaps", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let config = options(&format!("--flap-settle 5 --incident-report {}", path.display()));
+        let clock = Rc::new(MockClock::new());
+        let mut monitor = monitor(&config, &clock, hosts(1));
+        let mut polls = script(2, 3, 1);
+        polls.extend(script(2, 3, 1));
+        polls.extend(script(10, 0, 1));
+        let mut source = FakeSource::new(polls);
+
+        // Back up twice, but not for long enough to close the outage.
+        run(&mut monitor, &clock, &mut source, 14);
+        assert!(monitor.incident.is_some());
+        assert!(!path.exists());
+
+        run(&mut monitor, &clock, &mut source, 6);
+        assert!(monitor.incident.is_none());
+
+        let report = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(report.matches("Internet outage").count(), 1);
+        assert!(report.contains("Duration: 00:00:08"));
+    }
+
+    #[test]
+    fn outages_close_at_once_without_a_settle_time() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(1));
+        let mut polls = script(1, 2, 1);
+        polls.extend(script(1, 0, 1));
+        let mut source = FakeSource::new(polls);
+
+        run(&mut monitor, &clock, &mut source, 3);
+        assert!(monitor.tracker.settled().is_none());
+
+        run(&mut monitor, &clock, &mut source, 1);
+        assert_eq!(monitor.tracker.settled().map(|outage| outage.duration),
+            Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn waiting_for_a_failed_first_poll_is_not_uptime() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(3));
+
+        // The first poll takes the whole timeout to come back.
+        clock.advance(Duration::from_secs(2));
+        run(&mut monitor, &clock, &mut FakeSource::new(script(0, 4, 3)), 4);
+
+        assert!(monitor.tracker.is_down());
+        assert_eq!(monitor.tracker.total_uptime(), Duration::from_secs(0));
+        assert_eq!(monitor.tracker.session(), Duration::from_secs(4));
+        assert_eq!(monitor.tracker.uptime_percentage(), 0.0);
+    }
+
+    #[test]
+    fn the_daily_totals_start_over_at_midnight() {
+        let (config, clock) = (options(""), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(1));
+        let tm = time::at(clock.wall());
+        let since_midnight = tm.tm_hour * 60 * 60 + tm.tm_min * 60 + tm.tm_sec;
+
+        clock.advance(Duration::from_secs(24 * 60 * 60 - since_midnight as u64 - 5));
+
+        let mut polls = script(1, 2, 1);
+        polls.extend(script(4, 0, 1));
+        let mut source = FakeSource::new(polls);
+
+        run(&mut monitor, &clock, &mut source, 5);
+        assert_eq!(monitor.today.session(), Duration::from_secs(5));
+        assert_eq!(monitor.today.downtimes().len(), 1);
+
+        run(&mut monitor, &clock, &mut source, 2);
+        assert!(monitor.today.downtimes().is_empty());
+        assert_eq!(monitor.today.session(), Duration::from_secs(2));
+        assert_eq!(monitor.tracker.downtimes().len(), 1);
+    }
+
+    #[test]
+    fn link_state_needs_no_monitor() {
+        let strings = |hosts: &[&str]| {
+            hosts.iter().map(|host| host.to_string()).collect::<Vec<_>>()
+        };
+        let local = strings(&["127.0.0.1", "10.0.0.1"]);
+
+        assert_eq!(link_state(&options(""), &local, &strings(&["10.0.0.1"]), &[]), State::Down);
+        assert_eq!(link_state(&options("--count-local"), &local, &strings(&["10.0.0.1"]), &[]),
+            State::Up);
+
+        let config = options("--service web=10.0.0.1,10.0.0.2 --service dns=10.0.0.3");
+        assert_eq!(link_state(&config, &hosts(3), &strings(&["10.0.0.3"]), &[]), State::Down);
+        assert_eq!(link_state(&config, &hosts(3), &strings(&["10.0.0.1"]), &[]), State::Up);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let waits: Vec<u64> = (1..9).map(|failures| backoff(1, failures)).collect();
+
+        assert_eq!(waits, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff(1, u32::MAX), MAX_BACKOFF);
+        assert_eq!(backoff(90, 5), 90);
+    }
+
+    #[test]
+    fn backoff_resets_once_pinging_works() {
+        let (config, clock) = (options("--interval 5"), Rc::new(MockClock::new()));
+        let mut monitor = monitor(&config, &clock, hosts(2));
+        let mut polls = vec![Vec::new(); 6];
+        polls.extend(script(1, 0, 2));
+        polls.push(Vec::new());
+        let mut source = FakeSource::new(polls);
+        let mut waits = Vec::new();
+
+        for _ in 0..8 {
+            monitor.poll(&mut source, None, false);
+            waits.push(monitor.retry_in());
+
+            let wait = monitor.retry_in().unwrap_or(config.interval);
+            clock.advance(Duration::from_secs(wait));
+        }
+
+        assert_eq!(waits, [Some(5), Some(10), Some(20), Some(40), Some(60), Some(60), None,
+            Some(5)]);
+        // Every host counts as dropped while pinging fails.
+        assert!(monitor.tracker.is_down());
+        assert_eq!(monitor.history.get("10.0.0.1").map(|record| record.dropped), Some(true));
+    }
+
+    #[test]
+    fn hooks_are_told_pseudonyms_when_redacting() {
+        let clock = Rc::new(MockClock::new());
+        let mut labels = HashMap::new();
+        redact(&mut labels, &hosts(2));
+
+        let config = options("--redact");
+        let redacted = Monitor::new(&config, clock.clone(), hosts(2), labels);
+        assert_eq!(redacted.hook_hosts(), ["host-1", "host-2"]);
+
+        let config = options("");
+        assert_eq!(monitor(&config, &clock, hosts(2)).hook_hosts(), hosts(2));
+    }
+}
@@ -0,0 +1,76 @@
+use oping::Ping;
+use std::thread;
+use std::time::Duration;
+
+/// Loss and latency distribution from a burst of pings to one host.
+pub struct Burst {
+    pub sent: usize,
+    pub latencies: Vec<f64>,
+}
+
+impl Burst {
+    pub fn loss_percentage(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+
+        (self.sent - self.latencies.len()) as f64 / self.sent as f64 * 100.0
+    }
+
+    pub fn summary(&self) -> String {
+        if self.latencies.is_empty() {
+            return format!("{} sent, 0 received, 100% loss", self.sent);
+        }
+
+        let n = self.latencies.len() as f64;
+        let min = self.latencies.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = self.latencies.iter().cloned().fold(0.0, f64::max);
+        let avg = self.latencies.iter().sum::<f64>() / n;
+        let var = self.latencies.iter().map(|l| (l - avg) * (l - avg)).sum::<f64>() / n;
+
+        format!("{} sent, {} received, {:.0}% loss, min/avg/max/mdev {:.2}/{:.2}/{:.2}/{:.2} ms",
+            self.sent, self.latencies.len(), self.loss_percentage(),
+            min, avg, max, var.sqrt())
+    }
+}
+
+/// Pings `host` `count` times in quick succession.
+pub fn burst(host: &str, count: usize, timeout: f64) -> Burst {
+    let mut result = Burst {
+        sent: 0,
+        latencies: Vec::with_capacity(count),
+    };
+
+    for _ in 0..count {
+        let mut ping = Ping::new();
+
+        if ping.set_timeout(timeout).and_then(|_| ping.add_host(host)).is_err() {
+            break;
+        }
+
+        result.sent += 1;
+
+        if let Ok(responses) = ping.send() {
+            for resp in responses {
+                if resp.dropped == 0 {
+                    result.latencies.push(resp.latency_ms);
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    result
+}
+
+/// Runs a burst against `host` on a detached thread and logs the result
+/// under `name`, which is what the rest of the log calls the host.
+pub fn spawn_burst(host: &str, name: &str, count: usize, timeout: f64) {
+    let (host, name) = (host.to_string(), name.to_string());
+
+    thread::spawn(move || {
+        let burst = burst(&host, count, timeout);
+        info!("Burst probe of {}: {}", name, burst.summary());
+    });
+}
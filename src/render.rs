@@ -0,0 +1,970 @@
+use config::{Config, StatsLevel, Window, WorstBy};
+use history::HostHistory;
+use route::Link;
+use slo::LatencySlo;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use terminal;
+use time;
+use tracker::{Period, State, TimeTracker, SEVERITY_NAMES};
+use {format_duration, format_latency, format_percentage, is_local};
+use {LATENCY_DISPLAY_CAP, LATENCY_WARNING};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+
+/// Colors used to tell hosts apart, chosen to avoid the status colors.
+const COLOR_TABLE: [&str; 6] = [
+    "\x1b[36m", "\x1b[34m", "\x1b[37m", "\x1b[96m", "\x1b[94m", "\x1b[97m",
+];
+
+/// Number of cells per row in the host grid.
+const GRID_COLUMNS: usize = 32;
+
+/// Width of the longest bar in the outage histogram.
+const HISTOGRAM_WIDTH: usize = 30;
+
+/// Height of the latency chart's plot area. Its width follows the
+/// terminal; each column is one second.
+const CHART_HEIGHT: usize = 15;
+
+/// Terminal width assumed when neither `--width` nor the terminal gives one.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Narrowest the host name column gets, however short the names.
+const MIN_NAME_WIDTH: usize = 8;
+
+/// Columns each chart row spends before the plot: the indent, the axis
+/// label and its rule.
+const CHART_GUTTER: usize = 11;
+
+/// Narrowest the chart plot gets, even if that means wrapping.
+const MIN_CHART_WIDTH: usize = 10;
+
+/// Width of each host's column in the readings view.
+const READINGS_WIDTH: usize = 12;
+
+/// Percentage points by which sampled and timed uptime may differ before
+/// the difference is flagged.
+const SAMPLED_TOLERANCE: f64 = 1.0;
+
+/// Span, in seconds, over which the worst host is picked.
+const WORST_WINDOW: u64 = 5 * 60;
+
+/// Span, in seconds, of the min/avg/max shown with `--mtr-style`.
+const MTR_WINDOW: u64 = 60;
+
+/// Draws the dashboard. Each poll calls the `draw_` methods for the panels
+/// that are enabled, top to bottom as they should appear, then `present`.
+/// A method may not be called at all, so none may rely on another.
+pub trait Renderer {
+    fn draw_stats(&mut self, tracker: &TimeTracker, history: &HostHistory,
+        labels: &HashMap<String, String>);
+    fn draw_slo(&mut self, slo: &LatencySlo, value: Option<f64>, tracker: &TimeTracker);
+    /// Stats since local midnight.
+    fn draw_today(&mut self, today: &TimeTracker);
+    /// A one-line warning about the monitor itself.
+    fn draw_notice(&mut self, notice: &str);
+    /// The interface carrying the default route, when it is known.
+    fn draw_link(&mut self, link: Option<&Link>);
+    /// Latency and loss of two hosts side by side, with the difference.
+    fn draw_compare(&mut self, pair: &(String, String), labels: &HashMap<String, String>,
+        history: &HostHistory);
+    /// A minimal summary shown in place of everything else while idle.
+    fn draw_dimmed(&mut self, tracker: &TimeTracker);
+    fn draw_hosts(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        unresolved: &[String], history: &HostHistory);
+    fn draw_grid(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory);
+    fn draw_chart(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory);
+    /// The last `count` latency readings of each host in a column, newest
+    /// at the top.
+    fn draw_readings(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory, count: usize);
+    fn draw_outages(&mut self, outages: &[Period]);
+    fn draw_histogram(&mut self, histogram: &[usize; 4], labels: &[String; 4]);
+    fn present(&mut self);
+}
+
+/// Redraws the whole dashboard with plain ANSI escape codes.
+pub struct AnsiRenderer {
+    title: String,
+    /// Summary of the polling settings, shown beside the host list heading.
+    settings: String,
+    buffer: String,
+    max_outages: usize,
+    latency_precision: usize,
+    uptime_precision: usize,
+    floor_uptime: bool,
+    time_format: String,
+    stats: StatsLevel,
+    worst_by: WorstBy,
+    window: Option<Window>,
+    mtr_style: bool,
+    host_slas: HashMap<String, f64>,
+    /// Leave addresses out of the frame, as hosts go by pseudonyms.
+    redact: bool,
+    /// Hosts drawn first, one per line, even in the grid.
+    pinned: Vec<String>,
+    /// Columns available to draw in.
+    width: usize,
+    /// Set by `--width`; otherwise the width follows the terminal.
+    fixed_width: bool,
+    /// Show the health score.
+    health: bool,
+    /// Where to keep a plain-text copy of the latest frame.
+    screen_file: Option<String>,
+    /// Index into `COLOR_TABLE` for each host, assigned on first sight so
+    /// it stays put when hosts are added, removed or reordered.
+    colors: HashMap<String, usize>,
+}
+
+impl AnsiRenderer {
+    pub fn new(config: &Config) -> AnsiRenderer {
+        AnsiRenderer {
+            title: config.title.clone(),
+            settings: settings(config),
+            buffer: String::new(),
+            max_outages: 10,
+            latency_precision: config.latency_precision,
+            uptime_precision: config.uptime_precision,
+            floor_uptime: config.floor_uptime,
+            time_format: config.time_format.clone(),
+            stats: config.stats,
+            worst_by: config.worst_by,
+            window: config.window,
+            mtr_style: config.mtr_style,
+            host_slas: config.host_slas.clone(),
+            redact: config.redact,
+            pinned: config.pinned.clone(),
+            width: config.width.or_else(terminal::width).unwrap_or(DEFAULT_WIDTH),
+            fixed_width: config.width.is_some(),
+            health: config.health_half_life.is_some(),
+            screen_file: config.screen_file.clone(),
+            colors: HashMap::new(),
+        }
+    }
+
+    /// Returns the frame drawn so far without writing it out.
+    pub fn take_frame(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+
+    fn percent(&self, pct: f64) -> String {
+        format_percentage(pct, self.uptime_precision, self.floor_uptime)
+    }
+
+    fn latency(&self, ms: f64) -> String {
+        format_latency(ms, self.latency_precision)
+    }
+
+    /// Width of the host name column: wide enough for the longest of
+    /// `names`, but never more than a third of the terminal.
+    fn name_width<'a, I: Iterator<Item = &'a String>>(&self, names: I) -> usize {
+        let longest = names.map(|name| name.chars().count()).max().unwrap_or(0);
+        longest.min(self.width / 3).max(MIN_NAME_WIDTH)
+    }
+
+    /// The span for a windowed statistic: `--window` if given, otherwise
+    /// `default` seconds.
+    fn span(&self, default: u64) -> Duration {
+        self.window.map_or(Duration::from_secs(default), |window| window.span())
+    }
+
+    fn host_color(&mut self, host: &str) -> &'static str {
+        let next = self.colors.len();
+        let index = *self.colors.entry(host.to_string()).or_insert(next);
+
+        COLOR_TABLE[index % COLOR_TABLE.len()]
+    }
+}
+
+impl Renderer for AnsiRenderer {
+    fn draw_stats(&mut self, tracker: &TimeTracker, history: &HostHistory,
+        labels: &HashMap<String, String>) {
+        let (color, state) = match tracker.state() {
+            State::Up => (GREEN, "UP"),
+            State::Degraded => (YELLOW, "DEGRADED"),
+            State::Down => (RED, "DOWN"),
+        };
+
+        let uptime = self.percent(tracker.uptime_percentage());
+
+        let _ = writeln!(self.buffer, "{}uptime — {}{}  {}{}{}  {} over {}",
+            BOLD, self.title, RESET, color, state, RESET,
+            uptime,
+            format_duration(tracker.session()));
+
+        if self.health {
+            let score = tracker.health_score();
+            let color = if score >= 90.0 { GREEN } else if score >= 50.0 { YELLOW } else { RED };
+
+            let _ = writeln!(self.buffer, "Health: {}{:.0}{}/100", color, score, RESET);
+        }
+
+        if let Some(remaining) = tracker.settling() {
+            let _ = writeln!(self.buffer, "{}Settling: outage ends if up for {}s more{}",
+                YELLOW, remaining.as_secs(), RESET);
+        }
+
+        if let Some(left) = tracker.grace_left() {
+            let _ = writeln!(self.buffer, "{}Starting up: outages tracked in {}s{}",
+                YELLOW, left.as_secs(), RESET);
+        }
+
+        if let Some(recent) = tracker.recent_uptime_percentage() {
+            // Any failure in the window shows at once; this row is meant to
+            // be the earliest sign of trouble.
+            let color = if recent < 100.0 { RED } else { GREEN };
+            let window = tracker.recent_window().as_secs();
+
+            let window = if window % 60 == 0 {
+                format!("{} minutes", window / 60)
+            }
+            else {
+                format!("{} seconds", window)
+            };
+
+            let recent = self.percent(recent);
+
+            let _ = writeln!(self.buffer, "Last {}: {}{}{}{}",
+                window, BOLD, color, recent, RESET);
+        }
+
+        if self.stats == StatsLevel::Compact {
+            self.buffer.push('\n');
+            return;
+        }
+
+        if let Some(sampled) = tracker.sampled_uptime_percentage() {
+            let percent = self.percent(sampled);
+            let _ = write!(self.buffer, "Sampled: {} of {} polls", percent, tracker.polls());
+
+            if (sampled - tracker.uptime_percentage()).abs() > SAMPLED_TOLERANCE {
+                let _ = write!(self.buffer, "  {}differs from timed uptime; polls may be late{}",
+                    YELLOW, RESET);
+            }
+
+            self.buffer.push('\n');
+        }
+
+        let degraded = tracker.total_degraded();
+
+        if degraded > Duration::from_secs(0) {
+            let _ = writeln!(self.buffer, "{}Degraded{} {} ({}, {} periods)",
+                YELLOW, RESET,
+                format_duration(degraded),
+                self.percent(tracker.percentage(State::Degraded)),
+                tracker.degradations().len() + if tracker.state() == State::Degraded { 1 } else { 0 });
+        }
+
+        if let Some(duration) = tracker.downtime() {
+            let _ = writeln!(self.buffer, "{}Outage in progress: {}{}",
+                RED, format_duration(duration), RESET);
+        }
+
+        if !tracker.downtimes().is_empty() {
+            let counts: Vec<String> = SEVERITY_NAMES.iter()
+                .zip(tracker.histogram().iter())
+                .map(|(name, count)| format!("{}: {}", name, count))
+                .collect();
+
+            let _ = writeln!(self.buffer, "{}", counts.join(", "));
+        }
+
+        if history.hosts().len() > 1 {
+            let heading = match self.window {
+                Some(window) => format!("Worst, {}", window.label()),
+                None => "Worst".to_string(),
+            };
+
+            let worst = history.worst(self.worst_by, self.span(WORST_WINDOW))
+                .map(|(host, value)| (labels.get(host).map_or(host, |l| l.as_str()), value));
+
+            match worst {
+                Some((host, loss)) if self.worst_by == WorstBy::Loss => {
+                    let _ = writeln!(self.buffer, "{}: {} ({:.0}% loss)", heading, host, loss);
+                }
+                Some((host, p95)) => {
+                    let _ = writeln!(self.buffer, "{}: {} (p95 {})",
+                        heading, host, self.latency(p95));
+                }
+                None => {}
+            }
+        }
+
+        if self.stats == StatsLevel::Verbose {
+            if let Some(summary) = history.summary(self.span(60 * 60)) {
+                let loss = if summary.polls == 0 {
+                    0.0
+                }
+                else {
+                    summary.dropped as f64 / summary.polls as f64 * 100.0
+                };
+
+                let heading = match self.window {
+                    Some(window) => format!("Over the {}", window.label()),
+                    None => "Last hour".to_string(),
+                };
+
+                let _ = write!(self.buffer, "{}: {} polls, {:.2}% loss",
+                    heading, summary.polls, loss);
+
+                if let Some(avg) = summary.avg() {
+                    let _ = write!(self.buffer, ", latency {} / {} / {}",
+                        self.latency(summary.min),
+                        self.latency(avg),
+                        self.latency(summary.max));
+                }
+
+                self.buffer.push('\n');
+            }
+
+            let _ = writeln!(self.buffer, "History: {:.1} KB, {} raw polls per host",
+                history.memory() as f64 / 1024.0, history.capacity());
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_dimmed(&mut self, tracker: &TimeTracker) {
+        let uptime = self.percent(tracker.uptime_percentage());
+        let _ = writeln!(self.buffer, "{}uptime {}  {}{}", DIM, tracker.state().name(), uptime, RESET);
+    }
+
+    fn draw_notice(&mut self, notice: &str) {
+        let _ = writeln!(self.buffer, "{}{}{}\n", YELLOW, notice, RESET);
+    }
+
+    fn draw_link(&mut self, link: Option<&Link>) {
+        let link = match link {
+            Some(link) => link,
+            None => return,
+        };
+
+        match link.ssid {
+            Some(ref ssid) => {
+                let _ = writeln!(self.buffer, "Via {} ({})\n", link.interface, ssid);
+            }
+            None => {
+                let _ = writeln!(self.buffer, "Via {}\n", link.interface);
+            }
+        }
+    }
+
+    fn draw_today(&mut self, today: &TimeTracker) {
+        let outages = today.downtimes().len() + if today.is_down() { 1 } else { 0 };
+
+        let uptime = self.percent(today.uptime_percentage());
+
+        let _ = writeln!(self.buffer, "Today: {} over {}, {} outages\n",
+            uptime, format_duration(today.session()), outages);
+    }
+
+    fn draw_compare(&mut self, pair: &(String, String), labels: &HashMap<String, String>,
+        history: &HostHistory) {
+        let (ref a, ref b) = *pair;
+        let span = self.span(WORST_WINDOW);
+
+        let _ = writeln!(self.buffer, "{}Compare{}  {:<14}{:<14}difference",
+            BOLD, RESET, labels.get(a).unwrap_or(a), labels.get(b).unwrap_or(b));
+
+        let latency = |host: &String| history.get(host).and_then(|record| {
+            if record.dropped { None } else { record.latency }
+        });
+        let average = |host: &String| history.host_summary(host, span).and_then(|s| s.avg());
+        let loss = |host: &String| history.host_summary(host, span)
+            .filter(|summary| summary.polls > 0)
+            .map(|summary| summary.dropped as f64 / summary.polls as f64 * 100.0);
+
+        let rows = [
+            ("Latency", latency(a), latency(b), false),
+            ("Average", average(a), average(b), false),
+            ("Loss", loss(a), loss(b), true),
+        ];
+
+        for &(name, first, second, percent) in rows.iter() {
+            let show = |value: Option<f64>| match value {
+                Some(v) if percent => format!("{:.1}%", v),
+                Some(v) => self.latency(v),
+                None => "-".to_string(),
+            };
+
+            let _ = write!(self.buffer, "  {:<9}{:<14}{:<14}", name, show(first), show(second));
+
+            if let (Some(first), Some(second)) = (first, second) {
+                let delta = second - first;
+
+                // Lower is better for every row, so a positive difference
+                // means the second host is doing worse.
+                let color = if delta > 0.0 { RED } else if delta < 0.0 { GREEN } else { RESET };
+                let magnitude = if percent {
+                    format!("{:.1}%", delta.abs())
+                }
+                else {
+                    self.latency(delta.abs())
+                };
+
+                let _ = write!(self.buffer, "{}{}{}{}", color,
+                    if delta < 0.0 { "-" } else { "+" }, magnitude, RESET);
+            }
+
+            self.buffer.push('\n');
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_slo(&mut self, slo: &LatencySlo, value: Option<f64>, tracker: &TimeTracker) {
+        let value = value.map_or("-".to_string(), |v| self.latency(v));
+
+        match tracker.downtime() {
+            Some(duration) => {
+                let _ = writeln!(self.buffer, "Latency SLO: {}VIOLATED ({}){}  p{} {}",
+                    RED, format_duration(duration), RESET, slo.percentile, value);
+            }
+            None => {
+                let _ = writeln!(self.buffer, "Latency SLO: {}OK{}  p{} {}",
+                    GREEN, RESET, slo.percentile, value);
+            }
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_hosts(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        unresolved: &[String], history: &HostHistory) {
+        let _ = writeln!(self.buffer, "{}Hosts{}  {}", BOLD, RESET, self.settings);
+
+        let mut below_sla = Vec::new();
+
+        let pinned: Vec<&String> = self.pinned.iter()
+            .filter_map(|pin| hosts.iter().find(|host| *host == pin))
+            .collect();
+
+        // Loopback hosts go last, under their own heading, so that they are
+        // not mistaken for evidence that the network is up.
+        let (local, network): (Vec<&String>, Vec<&String>) = hosts.iter()
+            .filter(|host| !self.pinned.contains(host))
+            .partition(|host| is_local(host));
+
+        let above_local = pinned.len() + network.len();
+        let name_width = self.name_width(hosts.iter()
+            .map(|host| labels.get(host).unwrap_or(host)));
+
+        for (i, &address) in pinned.iter().chain(network.iter()).chain(local.iter()).enumerate() {
+            if i == above_local && above_local > 0 {
+                let _ = writeln!(self.buffer, "{}Local{}", BOLD, RESET);
+            }
+
+            let host_name = labels.get(address).unwrap_or(address);
+            let host_color = self.host_color(address);
+            let host = format!("{}{:<width$}{}",
+                host_color, ellipsize(host_name, name_width), RESET, width = name_width);
+
+            let record = match history.get(address) {
+                Some(record) => record,
+                None => {
+                    let _ = writeln!(self.buffer, "  {} ----", host);
+                    continue;
+                }
+            };
+
+            if record.dropped {
+                let _ = write!(self.buffer, "  {} {}FAIL{}{:15}", host, RED, RESET, "");
+            }
+            else {
+                let latency = record.latency.unwrap_or(0.0);
+                let color = if latency > LATENCY_WARNING { YELLOW } else { GREEN };
+
+                let _ = write!(self.buffer, "  {} {}PASS{} {:>11} {} ",
+                    host, color, RESET,
+                    self.latency(latency),
+                    history.host_trend(address, self.span(60 * 60)).arrow());
+            }
+
+            if self.mtr_style {
+                let window = self.span(MTR_WINDOW);
+                let summary = history.host_summary(address, window).unwrap();
+                let answered = summary.polls > summary.dropped;
+                let precision = self.latency_precision;
+                let value = |v: f64| {
+                    if !answered {
+                        "-".to_string()
+                    }
+                    else if v > LATENCY_DISPLAY_CAP {
+                        format!(">{:.0}", LATENCY_DISPLAY_CAP)
+                    }
+                    else {
+                        format!("{:.*}", precision, v)
+                    }
+                };
+
+                let triplet = format!("{}/{}/{}",
+                    value(summary.min), value(summary.avg().unwrap_or(0.0)), value(summary.max));
+                let _ = write!(self.buffer, "{:>20} ", triplet);
+            }
+
+            let uptime = history.host_uptime_percentage(address).unwrap();
+            let shown = self.percent(uptime);
+
+            match self.host_slas.get(address) {
+                Some(&sla) => {
+                    let color = if uptime >= sla { GREEN } else { RED };
+                    let _ = write!(self.buffer, "{}{:>8}{}", color, shown, RESET);
+
+                    if uptime < sla {
+                        below_sla.push(format!("{} ({} < {}%)", host_name, shown, sla));
+                    }
+                }
+                None => {
+                    let _ = write!(self.buffer, "{:>8}", shown);
+                }
+            }
+
+            match history.host_loss(address, self.span(60 * 60)).unwrap() {
+                (loss, Some(pattern)) => {
+                    let _ = write!(self.buffer, "  loss: {:.0}% ({})", loss, pattern.name());
+                }
+                (loss, None) if loss > 0.0 => {
+                    let _ = write!(self.buffer, "  loss: {:.0}%", loss);
+                }
+                _ => {}
+            }
+
+            if let (Some(old), Some(new)) = (history.host_address_change(address), record.address.as_ref()) {
+                if self.redact {
+                    let _ = write!(self.buffer, "  {}IP changed{}", MAGENTA, RESET);
+                }
+                else {
+                    let _ = write!(self.buffer, "  {}IP changed: {} → {}{}",
+                        MAGENTA, old, new, RESET);
+                }
+            }
+
+            if record.dropped {
+                match record.last_success {
+                    Some(at) => {
+                        let ago = (time::get_time() - at).num_seconds().max(0) as u64;
+
+                        let _ = write!(self.buffer, "  last OK: {} ({} ago)",
+                            time::strftime(&self.time_format, &time::at(at)).unwrap(),
+                            format_duration(Duration::from_secs(ago)));
+                    }
+                    None => self.buffer.push_str("  last OK: never"),
+                }
+            }
+
+            self.buffer.push('\n');
+        }
+
+        for note in unresolved {
+            let _ = writeln!(self.buffer, "  {}", note);
+        }
+
+        if !below_sla.is_empty() {
+            let _ = writeln!(self.buffer, "  {}Below SLA:{} {}", RED, RESET, below_sla.join(", "));
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_grid(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory) {
+        let up = hosts.iter()
+            .filter(|h| history.get(h).is_some_and(|record| !record.dropped))
+            .count();
+
+        let _ = writeln!(self.buffer, "{}Hosts{}  {} of {} responding", BOLD, RESET, up, hosts.len());
+
+        let pinned: Vec<&String> = self.pinned.iter()
+            .filter_map(|pin| hosts.iter().find(|host| *host == pin))
+            .collect();
+
+        let name_width = self.name_width(pinned.iter()
+            .map(|host| labels.get(*host).unwrap_or(host)));
+
+        for host in pinned {
+            let color = self.host_color(host);
+            let name = labels.get(host).unwrap_or(host);
+            let _ = write!(self.buffer, "  {}{:<width$}{} ",
+                color, ellipsize(name, name_width), RESET, width = name_width);
+
+            match history.get(host) {
+                Some(record) if record.dropped => {
+                    let _ = writeln!(self.buffer, "{}FAIL{}", RED, RESET);
+                }
+                Some(record) => {
+                    let _ = writeln!(self.buffer, "{}PASS{} {:>11}", GREEN, RESET,
+                        self.latency(record.latency.unwrap_or(0.0)));
+                }
+                None => {
+                    let _ = writeln!(self.buffer, "----");
+                }
+            }
+        }
+
+        let rest: Vec<String> = hosts.iter()
+            .filter(|host| !self.pinned.contains(host))
+            .cloned()
+            .collect();
+
+        for row in rest.chunks(GRID_COLUMNS) {
+            self.buffer.push_str("  ");
+
+            for host in row {
+                let color = match history.get(host) {
+                    Some(record) if record.dropped => RED,
+                    Some(_) => GREEN,
+                    None => RESET,
+                };
+
+                let _ = write!(self.buffer, "{}■{}", color, RESET);
+            }
+
+            self.buffer.push('\n');
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_chart(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory) {
+        let _ = writeln!(self.buffer, "{}Latency{}", BOLD, RESET);
+
+        let mut series = Vec::new();
+
+        for host in hosts {
+            if let Some(record) = history.get(host) {
+                series.push((self.host_color(host), record.raw_samples()));
+            }
+        }
+
+        let width = self.width.saturating_sub(CHART_GUTTER).max(MIN_CHART_WIDTH);
+
+        for row in render_chart(&series, Instant::now(), width, CHART_HEIGHT) {
+            let _ = writeln!(self.buffer, "  {}", row);
+        }
+
+        self.buffer.push_str("  ");
+
+        for host in hosts {
+            let color = self.host_color(host);
+            let _ = write!(self.buffer, "{}●{} {}  ", color, RESET, labels.get(host).unwrap_or(host));
+        }
+
+        self.buffer.push_str("\n\n");
+    }
+
+    fn draw_readings(&mut self, hosts: &[String], labels: &HashMap<String, String>,
+        history: &HostHistory, count: usize) {
+        let _ = write!(self.buffer, "{}Readings{}\n  ", BOLD, RESET);
+
+        for host in hosts {
+            let name: String = labels.get(host).unwrap_or(host)
+                .chars()
+                .take(READINGS_WIDTH - 1)
+                .collect();
+            let color = self.host_color(host);
+            let _ = write!(self.buffer, "{}{:<width$}{}", color, name, RESET, width = READINGS_WIDTH);
+        }
+
+        self.buffer.push('\n');
+
+        for row in 0..count {
+            self.buffer.push_str("  ");
+
+            for host in hosts {
+                let reading = history.get(host)
+                    .and_then(|record| record.raw_samples().iter().rev().nth(row));
+
+                let _ = match reading {
+                    Some(&(_, Some(latency))) => {
+                        let color = if latency > LATENCY_WARNING { YELLOW } else { GREEN };
+
+                        write!(self.buffer, "{}{:<width$}{}", color,
+                            self.latency(latency), RESET,
+                            width = READINGS_WIDTH)
+                    }
+                    Some(&(_, None)) => {
+                        write!(self.buffer, "{}{:<width$}{}", RED, "FAIL", RESET,
+                            width = READINGS_WIDTH)
+                    }
+                    None => write!(self.buffer, "{:<width$}", "", width = READINGS_WIDTH),
+                };
+            }
+
+            self.buffer.push('\n');
+        }
+
+        self.buffer.push('\n');
+    }
+
+    fn draw_outages(&mut self, outages: &[Period]) {
+        let _ = writeln!(self.buffer, "{}Outages ({}){}", BOLD, outages.len(), RESET);
+
+        if outages.is_empty() {
+            let _ = writeln!(self.buffer, "  {}No outages yet{}", GREEN, RESET);
+            return;
+        }
+
+        for period in outages.iter().rev().take(self.max_outages) {
+            let start = time::at(period.start);
+
+            let _ = write!(self.buffer, "  #{:<4} {}  {}",
+                period.id,
+                time::strftime(&self.time_format, &start).unwrap(),
+                format_duration(period.duration));
+
+            if period.flaps > 0 {
+                let _ = write!(self.buffer, "  {}({} flaps, up {}){}",
+                    YELLOW, period.flaps, format_duration(period.good), RESET);
+            }
+
+            if period.local_change {
+                let _ = write!(self.buffer, "  {}local network change{}", MAGENTA, RESET);
+            }
+
+            self.buffer.push('\n');
+        }
+    }
+
+    fn draw_histogram(&mut self, histogram: &[usize; 4], labels: &[String; 4]) {
+        let _ = writeln!(self.buffer, "\n{}Outage durations{}", BOLD, RESET);
+
+        let max = histogram.iter().cloned().max().unwrap_or(0);
+        let colors = [GREEN, YELLOW, YELLOW, RED];
+
+        for (i, &count) in histogram.iter().enumerate() {
+            let width = if max == 0 { 0 } else { (count * HISTOGRAM_WIDTH).div_ceil(max) };
+
+            let _ = writeln!(self.buffer, "  {:>6} {}{}{} {}",
+                labels[i], colors[i], "█".repeat(width), RESET, count);
+        }
+    }
+
+    fn present(&mut self) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+
+        let _ = write!(stdout, "\x1b[H\x1b[2J{}", self.buffer);
+        let _ = stdout.flush();
+
+        if let Some(path) = self.screen_file.take() {
+            let text = strip_ansi(&self.buffer);
+
+            match File::create(&path).and_then(|mut file| file.write_all(text.as_bytes())) {
+                Ok(()) => self.screen_file = Some(path),
+                Err(e) => warn!("Could not write screen to {}, giving up: {}", path, e),
+            }
+        }
+
+        self.buffer.clear();
+
+        // Picked up by the next frame, so the layout follows resizes.
+        if !self.fixed_width {
+            self.width = terminal::width().unwrap_or(self.width);
+        }
+    }
+}
+
+/// Describes the interval, timeout, down policy and latency thresholds,
+/// e.g. `1s/2s, all down, warn 100ms`.
+fn settings(config: &Config) -> String {
+    let policy = if config.services.is_empty() {
+        format!("{} down", config.down_when.name())
+    }
+    else {
+        "service quorum".to_string()
+    };
+
+    let mut settings = format!("{}s/{}s, {}, warn {}ms",
+        config.interval, config.timeout, policy, LATENCY_WARNING);
+
+    if let Some(threshold) = config.latency_down_threshold {
+        let _ = write!(settings, ", slow {}ms", threshold);
+    }
+
+    settings
+}
+
+/// Cuts `name` to `width` characters, ending in an ellipsis if it was
+/// longer.
+fn ellipsize(name: &str, width: usize) -> String {
+    if name.chars().count() <= width {
+        return name.to_string();
+    }
+
+    let mut cut: String = name.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Removes the escape sequences this renderer emits, leaving plain text.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the CSI introducer and parameters up to the final letter.
+            for c in &mut chars {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+        else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// A host's label with its recent samples, oldest first.
+type ChartSeries<'a> = (&'static str, &'a VecDeque<(Instant, Option<f64>)>);
+
+/// Plots each series' samples from the last `width` seconds as points on a
+/// `width` by `height` grid, auto-scaling the y-axis to the highest latency.
+/// Dropped polls are left as gaps. Returns the rows, top first, including
+/// the axes.
+fn render_chart(series: &[ChartSeries], now: Instant,
+    width: usize, height: usize) -> Vec<String> {
+    let span = Duration::from_secs(width as u64);
+    let in_window = |at: Instant| now.duration_since(at) < span;
+
+    let max = series.iter()
+        .flat_map(|&(_, samples)| samples.iter())
+        .filter(|&&(at, _)| in_window(at))
+        .filter_map(|&(_, latency)| latency)
+        .fold(0.0, f64::max);
+
+    let mut cells: Vec<Vec<Option<&'static str>>> = vec![vec![None; width]; height];
+
+    for &(color, samples) in series {
+        for &(at, latency) in samples.iter().filter(|&&(at, _)| in_window(at)) {
+            let latency = match latency {
+                Some(latency) => latency,
+                None => continue,
+            };
+
+            let age = now.duration_since(at).as_secs() as usize;
+            let column = width - 1 - age.min(width - 1);
+            let row = if max == 0.0 {
+                0
+            }
+            else {
+                ((latency / max) * (height - 1) as f64).round() as usize
+            };
+
+            cells[height - 1 - row][column] = Some(color);
+        }
+    }
+
+    let mut rows = Vec::with_capacity(height + 2);
+
+    for (i, line) in cells.iter().enumerate() {
+        let axis = if i == 0 {
+            format!("{:>7.1}", max)
+        }
+        else if i == height - 1 {
+            format!("{:>7.1}", 0.0)
+        }
+        else {
+            " ".repeat(7)
+        };
+
+        let mut row = format!("{} │", axis);
+
+        for cell in line {
+            match *cell {
+                Some(color) => {
+                    let _ = write!(row, "{}•{}", color, RESET);
+                }
+                None => row.push(' '),
+            }
+        }
+
+        rows.push(row);
+    }
+
+    rows.push(format!("{:>7} └{}", "ms", "─".repeat(width)));
+    let oldest = format!("-{}s", width);
+    let gap = width.saturating_sub(oldest.len() + 3);
+    rows.push(format!("{:9}{}{}now", "", oldest, " ".repeat(gap)));
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::SystemClock;
+    use tracker::{band_labels, OUTAGE_BANDS};
+
+    fn hosts_frame(latencies: &[(&str, f64)]) -> Vec<String> {
+        let mut renderer = AnsiRenderer::new(&Config::default());
+        let mut history = HostHistory::new(SystemClock, 0.2, 60);
+        let hosts: Vec<String> = latencies.iter().map(|&(host, _)| host.to_string()).collect();
+
+        for &(host, latency) in latencies {
+            history.update(host, false, latency);
+        }
+
+        renderer.draw_hosts(&hosts, &HashMap::new(), &[], &history);
+        strip_ansi(&renderer.take_frame()).lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn empty_tracker_renders_without_outages() {
+        let mut renderer = AnsiRenderer::new(&Config::default());
+        let tracker = TimeTracker::new(SystemClock);
+        let history = HostHistory::new(SystemClock, 0.2, 60);
+
+        renderer.draw_stats(&tracker, &history, &HashMap::new());
+        renderer.draw_today(&tracker);
+        renderer.draw_outages(tracker.downtimes());
+        renderer.draw_histogram(tracker.histogram(), &band_labels(&OUTAGE_BANDS));
+
+        let frame = strip_ansi(&renderer.take_frame());
+        assert!(frame.contains("Outages (0)"));
+        assert!(frame.contains("No outages yet"));
+    }
+
+    #[test]
+    fn chart_fits_the_terminal() {
+        let mut config = Config::default();
+        config.width = Some(60);
+        let mut renderer = AnsiRenderer::new(&config);
+        let mut history = HostHistory::new(SystemClock, 0.2, 60);
+        let hosts = vec!["10.0.0.1".to_string()];
+
+        history.update(&hosts[0], false, 20.0);
+        renderer.draw_chart(&hosts, &HashMap::new(), &history);
+
+        let frame = strip_ansi(&renderer.take_frame());
+        let widest = frame.lines().map(|line| line.chars().count()).max().unwrap();
+        assert_eq!(widest, 60);
+    }
+
+    #[test]
+    fn huge_latency_keeps_the_columns_aligned() {
+        let lines = hosts_frame(&[("10.0.0.1", 20.0), ("10.0.0.2", 10000.0)]);
+        let arrow = |line: &String| line.chars().position(|c| "→↑↓".contains(c));
+
+        assert!(lines[2].contains(">999 ms"));
+        assert!(arrow(&lines[1]).is_some());
+        assert_eq!(arrow(&lines[1]), arrow(&lines[2]));
+        assert_eq!(lines[1].chars().count(), lines[2].chars().count());
+    }
+}
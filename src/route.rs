@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::Read;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Seconds between SSID checks while the default route stays on one
+/// interface, since each check runs `iwgetid`.
+const SSID_REFRESH: u64 = 30;
+
+/// The interface carrying the default route and, for Wi-Fi, the network it
+/// is joined to.
+#[derive(Clone, PartialEq)]
+pub struct Link {
+    pub interface: String,
+    pub ssid: Option<String>,
+}
+
+impl Link {
+    /// The SSID, or the interface name for wired links.
+    pub fn name(&self) -> &str {
+        self.ssid.as_ref().unwrap_or(&self.interface)
+    }
+}
+
+/// Watches the default route so outages caused by switching networks or
+/// unplugging a cable can be told apart from remote ones. Only Linux is
+/// supported; elsewhere `/proc/net/route` is missing and nothing is seen.
+pub struct RouteWatch {
+    link: Option<Link>,
+    /// When the SSID was last asked for.
+    ssid_checked: Instant,
+}
+
+impl RouteWatch {
+    pub fn new() -> RouteWatch {
+        RouteWatch { link: default_interface().map(link), ssid_checked: Instant::now() }
+    }
+
+    pub fn link(&self) -> Option<&Link> {
+        self.link.as_ref()
+    }
+
+    /// Rereads the default route, returning true if it moved to another
+    /// interface, went away, or stayed on the same Wi-Fi interface but
+    /// joined another network since the last call. The SSID is only asked
+    /// for again when the interface changes or every `SSID_REFRESH` seconds,
+    /// so switching networks on one interface may take that long to show.
+    pub fn changed(&mut self) -> bool {
+        let current = match default_interface() {
+            Some(interface) => {
+                if self.stale(&interface) {
+                    self.ssid_checked = Instant::now();
+                    Some(link(interface))
+                }
+                else {
+                    self.link.clone()
+                }
+            }
+            None => None,
+        };
+
+        self.update(current)
+    }
+
+    /// Whether the cached SSID cannot be used for `interface`.
+    fn stale(&self, interface: &str) -> bool {
+        self.link.as_ref().map_or(true, |link| link.interface != interface)
+            || self.ssid_checked.elapsed() >= Duration::from_secs(SSID_REFRESH)
+    }
+
+    fn update(&mut self, current: Option<Link>) -> bool {
+        if current == self.link {
+            return false;
+        }
+
+        self.link = current;
+
+        true
+    }
+}
+
+fn link(interface: String) -> Link {
+    Link {
+        ssid: ssid(&interface),
+        interface,
+    }
+}
+
+/// The interface of the first default route in `/proc/net/route`.
+fn default_interface() -> Option<String> {
+    let mut table = String::new();
+    File::open("/proc/net/route").ok()?.read_to_string(&mut table).ok()?;
+
+    table.lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.len() > 1 && fields[1] == "00000000")
+        .map(|fields| fields[0].to_string())
+}
+
+/// Asks `iwgetid` for the SSID. Gives nothing for wired interfaces or if
+/// wireless tools are not installed.
+fn ssid(interface: &str) -> Option<String> {
+    let output = Command::new("iwgetid").arg("-r").arg(interface).output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let ssid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if ssid.is_empty() { None } else { Some(ssid) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wifi(ssid: &str) -> Option<Link> {
+        Some(Link { interface: "wlan0".to_string(), ssid: Some(ssid.to_string()) })
+    }
+
+    #[test]
+    fn switching_networks_on_one_interface_is_a_change() {
+        let mut watch = RouteWatch { link: wifi("home"), ssid_checked: Instant::now() };
+
+        assert!(!watch.update(wifi("home")));
+        assert!(watch.update(wifi("cafe")));
+        assert_eq!(watch.link().map(|link| link.name()), Some("cafe"));
+    }
+
+    #[test]
+    fn losing_the_route_is_a_change() {
+        let mut watch = RouteWatch { link: wifi("home"), ssid_checked: Instant::now() };
+
+        assert!(watch.update(None));
+        assert!(watch.link().is_none());
+        assert!(!watch.update(None));
+    }
+
+    #[test]
+    fn ssid_is_only_rechecked_when_stale() {
+        let mut watch = RouteWatch { link: wifi("home"), ssid_checked: Instant::now() };
+
+        assert!(!watch.stale("wlan0"));
+        assert!(watch.stale("eth0"));
+
+        watch.ssid_checked = Instant::now() - Duration::from_secs(SSID_REFRESH);
+        assert!(watch.stale("wlan0"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// How long raw polls are kept before being rolled up by the minute.
+const RAW_RETENTION: u64 = 60 * 60;
+
+/// Default cap on the number of raw polls kept per host; an hour's worth
+/// at one poll a second.
+pub const RAW_CAPACITY: usize = 3600;
+
+/// How long per-minute rollups are kept before being rolled up by the hour.
+const MINUTE_RETENTION: u64 = 24 * 60 * 60;
+
+/// Summary of the polls that fell within one minute or hour.
+#[derive(Clone, Debug)]
+pub struct Rollup {
+    pub start: Instant,
+    pub polls: usize,
+    pub dropped: usize,
+    pub min: f64,
+    pub max: f64,
+    sum: f64,
+}
+
+impl Rollup {
+    fn new(start: Instant) -> Rollup {
+        Rollup {
+            start,
+            polls: 0,
+            dropped: 0,
+            min: f64::INFINITY,
+            max: 0.0,
+            sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, latency: Option<f64>) {
+        self.polls += 1;
+
+        match latency {
+            Some(latency) => {
+                self.min = self.min.min(latency);
+                self.max = self.max.max(latency);
+                self.sum += latency;
+            }
+            None => self.dropped += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Rollup) {
+        self.polls += other.polls;
+        self.dropped += other.dropped;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+    }
+
+    /// Mean latency of the polls that got a response.
+    pub fn avg(&self) -> Option<f64> {
+        let answered = self.polls - self.dropped;
+
+        if answered == 0 {
+            None
+        }
+        else {
+            Some(self.sum / answered as f64)
+        }
+    }
+}
+
+/// Per-host poll history with tiered retention: raw polls for the last
+/// hour, per-minute rollups for the last day, and per-hour rollups beyond
+/// that, so memory stays bounded on multi-day runs.
+///
+/// The raw tier is shared by every consumer; each reads only the tail it
+/// needs. It is also capped at `capacity` polls, past which the oldest are
+/// rolled up early.
+pub struct Samples {
+    base: Instant,
+    capacity: usize,
+    raw: VecDeque<(Instant, Option<f64>)>,
+    minutes: VecDeque<Rollup>,
+    hours: Vec<Rollup>,
+}
+
+impl Samples {
+    pub fn new(base: Instant, capacity: usize) -> Samples {
+        Samples {
+            base,
+            capacity,
+            raw: VecDeque::new(),
+            minutes: VecDeque::new(),
+            hours: Vec::new(),
+        }
+    }
+
+    /// Records a poll; `None` marks a dropped poll.
+    pub fn push(&mut self, now: Instant, latency: Option<f64>) {
+        self.raw.push_back((now, latency));
+        self.roll_up(now);
+    }
+
+    /// Caps the raw tier at `capacity` polls, rolling up any beyond that.
+    pub fn set_capacity(&mut self, capacity: usize, now: Instant) {
+        self.capacity = capacity;
+        self.roll_up(now);
+    }
+
+    /// Discards the oldest rollup, hourly ones first. Returns false if
+    /// there were none left.
+    pub fn drop_oldest(&mut self) -> bool {
+        if !self.hours.is_empty() {
+            self.hours.remove(0);
+            true
+        }
+        else {
+            self.minutes.pop_front().is_some()
+        }
+    }
+
+    /// Rough number of bytes held, not counting allocator overhead or
+    /// spare capacity.
+    pub fn memory(&self) -> usize {
+        self.raw.len() * mem::size_of::<(Instant, Option<f64>)>()
+            + (self.minutes.len() + self.hours.len()) * mem::size_of::<Rollup>()
+    }
+
+    /// Moves raw polls past the retention or capacity into minute rollups,
+    /// and old minute rollups into hour rollups.
+    fn roll_up(&mut self, now: Instant) {
+        while let Some(&(at, latency)) = self.raw.front() {
+            let expired = now.duration_since(at).as_secs() >= RAW_RETENTION;
+
+            if self.raw.len() <= self.capacity && !expired {
+                break;
+            }
+
+            self.raw.pop_front();
+
+            let start = self.bucket(at, 60);
+            if self.minutes.back().map_or(true, |r| r.start != start) {
+                self.minutes.push_back(Rollup::new(start));
+            }
+            self.minutes.back_mut().unwrap().add(latency);
+        }
+
+        while self.minutes.front().is_some_and(|r| now.duration_since(r.start).as_secs() >= MINUTE_RETENTION) {
+            let minute = self.minutes.pop_front().unwrap();
+
+            let start = self.bucket(minute.start, 60 * 60);
+            if self.hours.last().map_or(true, |r| r.start != start) {
+                self.hours.push(Rollup::new(start));
+            }
+            self.hours.last_mut().unwrap().merge(&minute);
+        }
+    }
+
+    /// Start of the `width`-second bucket containing `at`.
+    fn bucket(&self, at: Instant, width: u64) -> Instant {
+        let offset = at.duration_since(self.base).as_secs();
+        self.base + Duration::from_secs(offset - offset % width)
+    }
+
+    /// Raw polls from the last hour, at most `capacity` of them, oldest
+    /// first.
+    pub fn raw(&self) -> &VecDeque<(Instant, Option<f64>)> {
+        &self.raw
+    }
+
+    /// Summarizes the polls from the last `span`, reading each part of the
+    /// range from the finest tier that still covers it. Rolled-up buckets
+    /// are included whole if they start within the range.
+    pub fn summary(&self, now: Instant, span: Duration) -> Rollup {
+        let from = if now.duration_since(self.base) > span { now - span } else { self.base };
+        let mut summary = Rollup::new(from);
+
+        for rollup in self.hours.iter().chain(self.minutes.iter()) {
+            if rollup.start >= from {
+                summary.merge(rollup);
+            }
+        }
+
+        for &(at, latency) in &self.raw {
+            if at >= from {
+                summary.add(latency);
+            }
+        }
+
+        summary
+    }
+}
@@ -0,0 +1,48 @@
+/// A named group of hosts that is considered down once `quorum` of its
+/// hosts have dropped.
+pub struct Service {
+    pub name: String,
+    pub hosts: Vec<String>,
+    pub quorum: usize,
+}
+
+impl Service {
+    /// Parses `NAME[:QUORUM]=HOST,HOST,...`. The quorum defaults to every
+    /// host in the service.
+    pub fn parse(spec: &str) -> Result<Service, String> {
+        let mut parts = spec.splitn(2, '=');
+        let head = parts.next().unwrap();
+        let hosts: Vec<String> = parts.next()
+            .ok_or_else(|| format!("Service `{}` has no hosts", spec))?
+            .split(',')
+            .filter(|h| !h.is_empty())
+            .map(|h| h.to_string())
+            .collect();
+
+        if hosts.is_empty() {
+            return Err(format!("Service `{}` has no hosts", spec));
+        }
+
+        let mut head = head.splitn(2, ':');
+        let name = head.next().unwrap().to_string();
+
+        let quorum = match head.next() {
+            Some(q) => q.parse().map_err(|_| format!("Invalid quorum for service {}: {}", name, q))?,
+            None => hosts.len(),
+        };
+
+        if quorum == 0 || quorum > hosts.len() {
+            return Err(format!("Quorum for service {} must be between 1 and {}", name, hosts.len()));
+        }
+
+        Ok(Service {
+            name,
+            hosts,
+            quorum,
+        })
+    }
+
+    pub fn is_down(&self, failed: &[String]) -> bool {
+        self.hosts.iter().filter(|h| failed.contains(h)).count() >= self.quorum
+    }
+}
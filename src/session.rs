@@ -0,0 +1,221 @@
+use format_duration;
+use json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::time::Duration;
+use tracker::State;
+
+/// Totals for a session recorded with `--stream-json --log-mode polls` (or
+/// `both`), for comparing runs after the fact.
+pub struct Summary {
+    /// Seconds from the first poll to the last.
+    span: f64,
+    /// Seconds spent in each state, indexed by `State as usize`.
+    time: [f64; 3],
+    outages: usize,
+    /// Polls and drops per host.
+    hosts: HashMap<String, (usize, usize)>,
+}
+
+impl Summary {
+    /// Reads the stream written to `path`.
+    pub fn load(path: &str) -> Result<Summary, String> {
+        let mut contents = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        Summary::read(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Totals the poll lines in `stream`. Transition lines are skipped, as
+    /// the polls already say everything they do.
+    pub fn read(stream: &str) -> Result<Summary, String> {
+        let mut summary = Summary {
+            span: 0.0,
+            time: [0.0; 3],
+            outages: 0,
+            hosts: HashMap::new(),
+        };
+        let mut first = None;
+        let mut last: Option<(f64, State)> = None;
+
+        for (i, line) in stream.lines().enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+            let value = json::parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+
+            if value.get("event").is_some() {
+                continue;
+            }
+
+            let ts = value.get("ts").and_then(|ts| ts.as_f64());
+            let state = value.get("state").and_then(|state| state.as_str()).and_then(state_named);
+
+            let (ts, state) = match (ts, state) {
+                (Some(ts), Some(state)) => (ts, state),
+                _ => return Err(format!("line {}: not a poll", i + 1)),
+            };
+
+            for host in value.get("hosts").and_then(|hosts| hosts.as_array()).unwrap_or(&[]) {
+                let name = host.get("host").and_then(|name| name.as_str());
+                let dropped = host.get("dropped").and_then(|dropped| dropped.as_bool());
+
+                if let (Some(name), Some(dropped)) = (name, dropped) {
+                    let counts = summary.hosts.entry(name.to_string()).or_insert((0, 0));
+                    counts.0 += 1;
+
+                    if dropped {
+                        counts.1 += 1;
+                    }
+                }
+            }
+
+            match last {
+                Some((at, previous)) => {
+                    summary.time[previous as usize] += (ts - at).max(0.0);
+
+                    if state == State::Down && previous != State::Down {
+                        summary.outages += 1;
+                    }
+                }
+                None if state == State::Down => summary.outages += 1,
+                None => {}
+            }
+
+            first = first.or(Some(ts));
+            last = Some((ts, state));
+        }
+
+        match (first, last) {
+            (Some(first), Some((last, _))) => summary.span = last - first,
+            _ => return Err("no polls; record with --log-mode polls or both".to_string()),
+        }
+
+        Ok(summary)
+    }
+
+    pub fn uptime_percentage(&self) -> f64 {
+        if self.span == 0.0 {
+            return 100.0;
+        }
+
+        self.time[State::Up as usize] / self.span * 100.0
+    }
+
+    /// Mean time between the starts of outages, counting only time the
+    /// link was not down.
+    pub fn mtbf(&self) -> Option<Duration> {
+        self.per_outage(self.span - self.time[State::Down as usize])
+    }
+
+    /// Mean length of an outage.
+    pub fn mttr(&self) -> Option<Duration> {
+        self.per_outage(self.time[State::Down as usize])
+    }
+
+    fn per_outage(&self, secs: f64) -> Option<Duration> {
+        match self.outages {
+            0 => None,
+            n => Some(Duration::from_millis((secs / n as f64 * 1000.0) as u64)),
+        }
+    }
+
+    /// The host that dropped the largest share of its polls, with that
+    /// share as a percentage. Hosts that never dropped are not counted.
+    pub fn worst(&self) -> Option<(&str, f64)> {
+        let mut worst: Option<(&str, f64)> = None;
+
+        for (host, &(polls, drops)) in &self.hosts {
+            let loss = drops as f64 / polls as f64 * 100.0;
+
+            if drops > 0 && worst.map_or(true, |(name, highest)| {
+                loss > highest || (loss == highest && host.as_str() < name)
+            }) {
+                worst = Some((host, loss));
+            }
+        }
+
+        worst
+    }
+}
+
+/// Lays out `a` and `b` side by side, one statistic per row, under their
+/// labels.
+pub fn compare(a: (&str, &Summary), b: (&str, &Summary)) -> String {
+    let cells = |summary: &Summary| {
+        let duration = |dur: Option<Duration>| dur.map_or("-".to_string(), format_duration);
+
+        vec![
+            format_duration(Duration::from_secs(summary.span as u64)),
+            format!("{:.2}%", summary.uptime_percentage()),
+            summary.outages.to_string(),
+            duration(summary.mtbf()),
+            duration(summary.mttr()),
+            summary.worst()
+                .map_or("-".to_string(), |(host, loss)| format!("{} ({:.1}%)", host, loss)),
+        ]
+    };
+
+    let names = ["Session", "Uptime", "Outages", "MTBF", "MTTR", "Worst host"];
+    let (left, right) = (cells(a.1), cells(b.1));
+    let width = left.iter().map(|cell| cell.len()).chain(Some(a.0.len())).max().unwrap_or(0);
+
+    let mut out = format!("{:<12}{:<width$}  {}\n", "", a.0, b.0, width = width);
+
+    for ((name, left), right) in names.iter().zip(&left).zip(&right) {
+        out.push_str(&format!("{:<12}{:<width$}  {}\n", name, left, right, width = width));
+    }
+
+    out
+}
+
+fn state_named(name: &str) -> Option<State> {
+    [State::Up, State::Degraded, State::Down].iter().cloned().find(|state| state.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json::{self, HostResult};
+    use time::Timespec;
+
+    fn poll(sec: i64, state: State, dropped: bool) -> String {
+        let hosts = [
+            HostResult { host: "1.1.1.1".to_string(), dropped, latency_ms: 10.0 },
+            HostResult { host: "8.8.8.8".to_string(), dropped: false, latency_ms: 12.0 },
+        ];
+
+        json::poll(&Timespec::new(sec, 0), "home", Some("wlan0"), &hosts, state)
+    }
+
+    #[test]
+    fn totals_a_recorded_stream() {
+        let stream = [
+            poll(0, State::Up, false),
+            poll(10, State::Down, true),
+            json::transition(&Timespec::new(10, 0), "home", State::Up, State::Down,
+                Duration::from_secs(10)),
+            poll(20, State::Up, false),
+            poll(30, State::Down, true),
+            poll(40, State::Up, false),
+        ].join("\n");
+
+        let summary = Summary::read(&stream).unwrap();
+
+        assert_eq!(summary.outages, 2);
+        assert_eq!(summary.uptime_percentage(), 50.0);
+        assert_eq!(summary.mttr(), Some(Duration::from_secs(10)));
+        assert_eq!(summary.mtbf(), Some(Duration::from_secs(10)));
+        assert_eq!(summary.worst(), Some(("1.1.1.1", 40.0)));
+    }
+
+    #[test]
+    fn rejects_streams_without_polls() {
+        let stream = json::transition(&Timespec::new(0, 0), "home", State::Up, State::Down,
+            Duration::from_secs(1));
+
+        assert!(Summary::read(&stream).is_err());
+        assert!(Summary::read("{\"ts\":1").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::cmp;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SIGINT: c_int = 2;
+const SIGTERM: c_int = 15;
+
+/// Longest the poll loop sleeps before checking whether to stop.
+const WAKE_EVERY: u64 = 100;
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+}
+
+extern "C" fn request_stop(_: c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+/// Makes SIGINT and SIGTERM ask the poll loop to stop rather than kill
+/// the process, so it can clean up after itself.
+pub fn install() {
+    unsafe {
+        signal(SIGINT, request_stop);
+        signal(SIGTERM, request_stop);
+    }
+}
+
+/// Whether SIGINT or SIGTERM has arrived since `install`.
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration`, or until a stop is requested.
+pub fn sleep(duration: Duration) {
+    let until = Instant::now() + duration;
+
+    while !stop_requested() {
+        let now = Instant::now();
+
+        if now >= until {
+            break;
+        }
+
+        thread::sleep(cmp::min(until - now, Duration::from_millis(WAKE_EVERY)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_stop_request_cuts_the_sleep_short() {
+        let started = Instant::now();
+
+        request_stop(SIGTERM);
+        sleep(Duration::from_secs(10));
+
+        assert!(stop_requested());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+/// Longest window an SLO may cover; older polls are no longer kept raw.
+const MAX_WINDOW: u64 = 60 * 60;
+
+/// A latency objective such as "95% of polls answer in under 50 ms over
+/// the last five minutes".
+pub struct LatencySlo {
+    pub percentile: f64,
+    pub threshold: f64,
+    pub window: Duration,
+}
+
+impl LatencySlo {
+    /// Parses `pPCT<MS over WINDOW`, e.g. `p95<50 over 5m`. The window
+    /// takes an `s`, `m` or `h` suffix.
+    pub fn parse(spec: &str) -> Result<LatencySlo, String> {
+        let invalid = || format!("Invalid latency SLO `{}`, expected e.g. `p95<50 over 5m`", spec);
+        let words: Vec<&str> = spec.split_whitespace().collect();
+
+        if words.len() != 3 || words[1] != "over" || !words[0].starts_with('p') {
+            return Err(invalid());
+        }
+
+        let mut target = words[0][1..].splitn(2, '<');
+        let percentile: f64 = target.next().and_then(|p| p.parse().ok()).ok_or_else(&invalid)?;
+        let threshold: f64 = target.next().and_then(|t| t.parse().ok()).ok_or_else(&invalid)?;
+
+        if percentile <= 0.0 || percentile > 100.0 || threshold <= 0.0 {
+            return Err(invalid());
+        }
+
+        let window = parse_window(words[2]).ok_or_else(&invalid)?;
+
+        if window == 0 || window > MAX_WINDOW {
+            return Err(format!("Latency SLO window must be between 1s and {}s", MAX_WINDOW));
+        }
+
+        Ok(LatencySlo {
+            percentile,
+            threshold,
+            window: Duration::from_secs(window),
+        })
+    }
+
+    /// Whether `value`, the measured percentile, meets the objective.
+    pub fn is_met(&self, value: f64) -> bool {
+        value < self.threshold
+    }
+}
+
+fn parse_window(window: &str) -> Option<u64> {
+    let (at, _) = window.char_indices().last()?;
+    let (number, unit) = window.split_at(at);
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok().and_then(|n| n.checked_mul(scale))
+}
+
+/// Nearest-rank percentile of `values`, or `None` if there are none.
+pub fn percentile(values: &mut [f64], percentile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+    Some(values[rank.max(1) - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_window_with_a_unit() {
+        let slo = LatencySlo::parse("p95<50 over 5m").unwrap();
+
+        assert_eq!(slo.percentile, 95.0);
+        assert_eq!(slo.threshold, 50.0);
+        assert_eq!(slo.window, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn rejects_a_multibyte_unit() {
+        assert!(LatencySlo::parse("p95<50 over 5é").is_err());
+        assert!(LatencySlo::parse("p95<50 over é").is_err());
+        assert_eq!(parse_window(""), None);
+    }
+}
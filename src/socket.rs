@@ -0,0 +1,87 @@
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Serves the latest status line to anyone who connects to a Unix socket,
+/// then hangs up. Connections are answered on a background thread from
+/// a snapshot the main loop replaces after every poll.
+pub struct StatusSocket {
+    snapshot: Arc<Mutex<String>>,
+}
+
+impl StatusSocket {
+    /// Listens on `path`, replacing a socket left behind by an earlier run.
+    pub fn start(path: &str) -> io::Result<StatusSocket> {
+        remove(path)?;
+
+        let listener = UnixListener::bind(path)?;
+        let snapshot = Arc::new(Mutex::new(String::from("{}")));
+        let shared = snapshot.clone();
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let line = shared.lock().unwrap().clone();
+                let _ = writeln!(stream, "{}", line);
+            }
+        });
+
+        Ok(StatusSocket { snapshot })
+    }
+
+    pub fn update(&self, line: String) {
+        *self.snapshot.lock().unwrap() = line;
+    }
+}
+
+/// Removes the socket at `path`, if there is one. Anything else found
+/// there is left alone and reported as an error, so a mistyped path
+/// cannot destroy a file.
+pub fn remove(path: &str) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(ErrorKind::AlreadyExists, "not a socket")),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+    use std::process;
+
+    fn scratch(name: &str) -> String {
+        let path = env::temp_dir().join(format!("uptime-test-{}-{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn an_old_socket_is_replaced() {
+        let path = scratch("old-socket");
+        drop(UnixListener::bind(&path).unwrap());
+
+        let socket = StatusSocket::start(&path);
+        assert!(socket.is_ok());
+
+        remove(&path).unwrap();
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[test]
+    fn other_files_are_left_alone() {
+        let path = scratch("notes.txt");
+        fs::write(&path, "keep me").unwrap();
+
+        assert!(StatusSocket::start(&path).is_err());
+        assert!(remove(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
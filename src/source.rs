@@ -0,0 +1,124 @@
+use error::MonitorError;
+use oping::Ping;
+#[cfg(test)]
+use oping::PingError;
+
+/// One host's answer to a poll.
+pub struct Response {
+    pub hostname: String,
+    /// Address the hostname resolved to, empty if unknown.
+    pub address: String,
+    pub dropped: bool,
+    pub latency_ms: f64,
+}
+
+/// Pings a set of hosts once per call. The monitor only sees responses
+/// through this, so tests can script them.
+pub trait ResponseSource {
+    /// Pings every host that can be added, returning the responses along
+    /// with the hosts that could not be.
+    fn poll(&mut self, hosts: &[String]) -> Result<(Vec<Response>, Vec<String>), MonitorError>;
+}
+
+/// ICMP echo through liboping.
+pub struct OpingSource {
+    /// Seconds to wait for each reply.
+    timeout: f64,
+}
+
+impl OpingSource {
+    pub fn new(timeout: f64) -> OpingSource {
+        OpingSource { timeout }
+    }
+}
+
+impl ResponseSource for OpingSource {
+    fn poll(&mut self, hosts: &[String]) -> Result<(Vec<Response>, Vec<String>), MonitorError> {
+        let mut ping = Ping::new();
+        ping.set_timeout(self.timeout)?;
+
+        let rejected = hosts.iter()
+            .filter(|host| ping.add_host(host).is_err())
+            .cloned()
+            .collect();
+
+        let responses = ping.send()?
+            .map(|item| Response {
+                hostname: item.hostname,
+                address: item.address,
+                dropped: item.dropped > 0,
+                latency_ms: item.latency_ms,
+            })
+            .collect();
+
+        Ok((responses, rejected))
+    }
+}
+
+/// Plays back a script with one entry per poll, each holding a latency
+/// per host in the order the hosts are passed, or `None` for a drop. An
+/// empty entry fails the whole poll, as when pinging is not permitted.
+#[cfg(test)]
+pub struct FakeSource {
+    script: ::std::collections::VecDeque<Vec<Option<f64>>>,
+    /// Hosts to report as rejected instead of answering.
+    pub rejected: Vec<String>,
+}
+
+#[cfg(test)]
+impl FakeSource {
+    pub fn new(script: Vec<Vec<Option<f64>>>) -> FakeSource {
+        FakeSource {
+            script: script.into_iter().collect(),
+            rejected: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ResponseSource for FakeSource {
+    fn poll(&mut self, hosts: &[String]) -> Result<(Vec<Response>, Vec<String>), MonitorError> {
+        let poll = self.script.pop_front().expect("script ran out of polls");
+
+        if poll.is_empty() {
+            let failure = PingError::LibOpingError("scripted failure".to_string());
+            return Err(MonitorError::Ping(failure));
+        }
+
+        assert_eq!(poll.len(), hosts.len(), "script does not match the hosts");
+
+        let (rejected, accepted): (Vec<_>, Vec<_>) = hosts.iter().zip(poll)
+            .partition(|&(host, _)| self.rejected.contains(host));
+
+        let responses = accepted.into_iter()
+            .map(|(host, latency)| Response {
+                hostname: host.clone(),
+                address: String::new(),
+                dropped: latency.is_none(),
+                latency_ms: latency.unwrap_or(0.0),
+            })
+            .collect();
+
+        Ok((responses, rejected.into_iter().map(|(host, _)| host.clone()).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_source_plays_its_script_in_order() {
+        let hosts = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+        let mut source = FakeSource::new(vec![vec![Some(10.0), None], vec![None, Some(20.0)]]);
+        source.rejected.push(hosts[1].clone());
+
+        let (responses, rejected) = source.poll(&hosts).unwrap();
+        assert_eq!(responses.len(), 1);
+        assert!(!responses[0].dropped);
+        assert_eq!(rejected, ["10.0.0.2"]);
+
+        let (responses, _) = source.poll(&hosts).unwrap();
+        assert!(responses[0].dropped);
+    }
+}
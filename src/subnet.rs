@@ -0,0 +1,38 @@
+use std::net::Ipv4Addr;
+
+/// Smallest prefix length accepted, to avoid accidentally sweeping a /8.
+const MIN_PREFIX: u32 = 22;
+
+/// Expands an IPv4 CIDR such as `192.168.1.0/24` into its host addresses,
+/// leaving out the network and broadcast addresses where they exist.
+pub fn expand(cidr: &str) -> Result<Vec<String>, String> {
+    let mut parts = cidr.splitn(2, '/');
+
+    let addr: Ipv4Addr = parts.next().unwrap().parse()
+        .map_err(|_| format!("Invalid subnet address: {}", cidr))?;
+    let prefix: u32 = parts.next()
+        .ok_or_else(|| format!("Subnet {} is missing a prefix length", cidr))?
+        .parse()
+        .map_err(|_| format!("Invalid subnet prefix: {}", cidr))?;
+
+    if prefix > 32 {
+        return Err(format!("Invalid subnet prefix: {}", cidr));
+    }
+
+    if prefix < MIN_PREFIX {
+        return Err(format!("Subnet {} is too large, the limit is /{}", cidr, MIN_PREFIX));
+    }
+
+    let mask = if prefix == 0 { 0 } else { !0u32 << (32 - prefix) };
+    let network = u32::from(addr) & mask;
+    let size = 1u32 << (32 - prefix);
+
+    let (first, last) = if size > 2 {
+        (network + 1, network + size - 2)
+    }
+    else {
+        (network, network + size - 1)
+    };
+
+    Ok((first..last + 1).map(|ip| Ipv4Addr::from(ip).to_string()).collect())
+}
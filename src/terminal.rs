@@ -0,0 +1,34 @@
+use std::env;
+use std::os::raw::{c_int, c_ulong, c_ushort};
+
+const STDOUT: c_int = 1;
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+const TIOCGWINSZ: c_ulong = 0x4008_7468;
+#[cfg(not(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd")))]
+const TIOCGWINSZ: c_ulong = 0x5413;
+
+#[repr(C)]
+#[derive(Default)]
+struct WinSize {
+    rows: c_ushort,
+    cols: c_ushort,
+    x_pixels: c_ushort,
+    y_pixels: c_ushort,
+}
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Columns in the terminal on stdout right now, falling back to `$COLUMNS`
+/// when stdout is not a terminal.
+pub fn width() -> Option<usize> {
+    let mut size = WinSize::default();
+
+    if unsafe { ioctl(STDOUT, TIOCGWINSZ, &mut size as *mut WinSize) } == 0 && size.cols > 0 {
+        return Some(size.cols as usize);
+    }
+
+    env::var("COLUMNS").ok().and_then(|cols| cols.parse().ok())
+}
@@ -0,0 +1,679 @@
+use clock::{Clock, SystemClock};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use time::{self, Timespec};
+
+/// Default upper bounds, in seconds, of all but the last outage duration
+/// bucket.
+pub const OUTAGE_BANDS: [u64; 3] = [10, 60, 300];
+
+/// Names for the outage duration buckets, shortest first.
+pub const SEVERITY_NAMES: [&str; 4] = ["Blips", "Minor", "Major", "Severe"];
+
+/// Default half-life, in seconds, of the health score.
+const DEFAULT_HALF_LIFE: u64 = 60 * 60;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    Up,
+    /// Reachable, but too slow to be useful.
+    Degraded,
+    Down,
+}
+
+impl State {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            State::Up => "up",
+            State::Degraded => "degraded",
+            State::Down => "down",
+        }
+    }
+}
+
+/// A finished outage or degradation.
+pub struct Period {
+    /// Sequence number, starting at 1, assigned when the period is
+    /// recorded and never reused.
+    pub id: usize,
+    pub start: Timespec,
+    pub duration: Duration,
+    /// Brief recoveries folded into this outage by the settle time.
+    pub flaps: usize,
+    /// Time spent up during those recoveries, included in `duration`.
+    pub good: Duration,
+    /// The default route changed during the outage, so it was probably
+    /// caused by switching networks rather than by the remote end.
+    pub local_change: bool,
+}
+
+/// An outage that has started but not yet been recorded, because the link
+/// has not stayed up for the settle time since.
+struct OpenOutage {
+    start: Instant,
+    start_wall: Timespec,
+    flaps: usize,
+    good: Duration,
+    local_change: bool,
+    /// When the link last came back, if it is not down right now.
+    recovered: Option<Instant>,
+}
+
+/// Link-level state accounting. All time is accumulated from a single
+/// checkpoint so the up, degraded and down totals always add up to the
+/// session length.
+pub struct TimeTracker<C: Clock = SystemClock> {
+    clock: C,
+    start: Instant,
+    start_wall: Timespec,
+    last: Instant,
+    up: Duration,
+    degraded: Duration,
+    down: Duration,
+    state: State,
+    since: Instant,
+    since_wall: Timespec,
+    downtimes: Vec<Period>,
+    degradations: Vec<Period>,
+    bands: [u64; 3],
+    histogram: [usize; 4],
+    /// How long the link must stay up before an outage is recorded.
+    settle: Duration,
+    outage: Option<OpenOutage>,
+    /// Whether the last `set_state` recorded an outage.
+    settled: bool,
+    /// Number of `set_state` calls per state, in `State` order.
+    polls: [usize; 3],
+    /// `set_state` calls from the last `recent_window`, oldest first.
+    recent: VecDeque<(Instant, State)>,
+    recent_window: Duration,
+    /// Time-decayed availability, 0 to 100, as of `last`.
+    health: f64,
+    half_life: Duration,
+    /// How long after the session starts failures are ignored.
+    grace: Duration,
+}
+
+impl<C: Clock> TimeTracker<C> {
+    pub fn new(clock: C) -> TimeTracker<C> {
+        let now = clock.now();
+        let wall = clock.wall();
+
+        TimeTracker {
+            clock,
+            start: now,
+            start_wall: wall,
+            last: now,
+            up: Duration::from_secs(0),
+            degraded: Duration::from_secs(0),
+            down: Duration::from_secs(0),
+            state: State::Up,
+            since: now,
+            since_wall: wall,
+            downtimes: Vec::new(),
+            degradations: Vec::new(),
+            bands: OUTAGE_BANDS,
+            histogram: [0; 4],
+            settle: Duration::from_secs(0),
+            outage: None,
+            settled: false,
+            polls: [0; 3],
+            recent: VecDeque::new(),
+            recent_window: Duration::from_secs(5 * 60),
+            health: 100.0,
+            half_life: Duration::from_secs(DEFAULT_HALF_LIFE),
+            grace: Duration::from_secs(0),
+        }
+    }
+
+    /// Sets how long it takes for time in one state to count for half as
+    /// much in `health_score`.
+    pub fn set_half_life(&mut self, half_life: Duration) {
+        self.half_life = half_life;
+    }
+
+    /// Treats the link as up for the first `grace` of the session, so an
+    /// outage while the network is still coming up is not recorded. Applies
+    /// again after `reset`.
+    pub fn set_grace(&mut self, grace: Duration) {
+        self.grace = grace;
+    }
+
+    /// Sets the span covered by `recent_uptime_percentage`.
+    pub fn set_recent_window(&mut self, window: Duration) {
+        self.recent_window = window;
+    }
+
+    /// Folds outages separated by less than `settle` of uptime into one.
+    pub fn set_settle(&mut self, settle: Duration) {
+        self.settle = settle;
+    }
+
+    /// Replaces the outage duration buckets. Only affects outages that end
+    /// afterwards.
+    pub fn set_bands(&mut self, bands: [u64; 3]) {
+        self.bands = bands;
+    }
+
+    /// Starts the session over from now, in the `Up` state, discarding
+    /// everything recorded so far.
+    pub fn reset(&mut self) {
+        let now = self.clock.now();
+        let wall = self.clock.wall();
+
+        self.start = now;
+        self.start_wall = wall;
+        self.last = now;
+        self.up = Duration::from_secs(0);
+        self.degraded = Duration::from_secs(0);
+        self.down = Duration::from_secs(0);
+        self.state = State::Up;
+        self.since = now;
+        self.since_wall = wall;
+        self.downtimes.clear();
+        self.degradations.clear();
+        self.histogram = [0; 4];
+        self.outage = None;
+        self.settled = false;
+        self.polls = [0; 3];
+        self.recent.clear();
+        self.health = 100.0;
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last);
+        self.health = self.decayed_health(elapsed);
+
+        match self.state {
+            State::Up => self.up += elapsed,
+            State::Degraded => self.degraded += elapsed,
+            State::Down => self.down += elapsed,
+        }
+
+        self.last = now;
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.state == State::Down
+    }
+
+    /// Length of the outage in progress, if any.
+    pub fn downtime(&self) -> Option<Duration> {
+        if self.is_down() {
+            Some(self.clock.now().duration_since(self.since))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Moves the link to `state`. If that is a change, returns the state
+    /// that ended along with how long it lasted.
+    pub fn set_state(&mut self, state: State) -> Option<(State, Duration)> {
+        let now = self.clock.now();
+        let state = if self.grace_left().is_some() { State::Up } else { state };
+        self.settled = false;
+        self.advance(now);
+        self.settle_outage(now);
+        self.polls[state as usize] += 1;
+        self.recent.push_back((now, state));
+
+        let window = self.recent_window;
+
+        while self.recent.front().is_some_and(|&(at, _)| now.duration_since(at) > window) {
+            self.recent.pop_front();
+        }
+
+        if state == self.state {
+            return None;
+        }
+
+        let ended = self.state;
+        let duration = now.duration_since(self.since);
+
+        match ended {
+            State::Down => {
+                if let Some(ref mut outage) = self.outage {
+                    outage.recovered = Some(now);
+                }
+
+                self.settle_outage(now);
+            }
+            State::Degraded => self.degradations.push(Period {
+                id: self.degradations.len() + 1,
+                start: self.since_wall,
+                duration,
+                flaps: 0,
+                good: Duration::from_secs(0),
+                local_change: false,
+            }),
+            State::Up => {}
+        }
+
+        if state == State::Down {
+            match self.outage {
+                Some(ref mut outage) => {
+                    if let Some(recovered) = outage.recovered.take() {
+                        outage.good += now.duration_since(recovered);
+                    }
+
+                    outage.flaps += 1;
+                }
+                None => {
+                    self.outage = Some(OpenOutage {
+                        start: now,
+                        start_wall: self.clock.wall(),
+                        flaps: 0,
+                        good: Duration::from_secs(0),
+                        local_change: false,
+                        recovered: None,
+                    });
+                }
+            }
+        }
+
+        self.state = state;
+        self.since = now;
+        self.since_wall = self.clock.wall();
+
+        Some((ended, duration))
+    }
+
+    /// Records the open outage once the link has been back for the settle
+    /// time.
+    fn settle_outage(&mut self, now: Instant) {
+        let recovered = match self.outage {
+            Some(OpenOutage { recovered: Some(recovered), .. }) => recovered,
+            _ => return,
+        };
+
+        if now.duration_since(recovered) < self.settle {
+            return;
+        }
+
+        let outage = self.outage.take().unwrap();
+        let duration = recovered.duration_since(outage.start);
+
+        let bucket = self.bands.iter()
+            .position(|&bound| duration.as_secs() < bound)
+            .unwrap_or(self.bands.len());
+
+        self.histogram[bucket] += 1;
+        self.settled = true;
+        self.downtimes.push(Period {
+            id: self.downtimes.len() + 1,
+            start: outage.start_wall,
+            duration,
+            flaps: outage.flaps,
+            good: outage.good,
+            local_change: outage.local_change,
+        });
+    }
+
+    /// The session drawn as `width` characters, oldest first: `_` where
+    /// the link was down at any point, otherwise `~` where it was degraded
+    /// and `#` where it was up throughout.
+    pub fn timeline(&self, width: usize) -> String {
+        let session = as_secs_f64(self.session());
+        let mut marks = vec!['#'; width];
+
+        if session == 0.0 {
+            return marks.into_iter().collect();
+        }
+
+        let offset = |ts: Timespec| (ts - self.start_wall).num_milliseconds() as f64 / 1000.0;
+        let mut paint = |start: f64, end: f64, mark: char| {
+            let first = (start / session * width as f64).floor().max(0.0) as usize;
+            let last = (end / session * width as f64).ceil().min(width as f64) as usize;
+
+            for slot in marks.iter_mut().take(last).skip(first) {
+                if mark == '_' || *slot == '#' {
+                    *slot = mark;
+                }
+            }
+        };
+
+        for period in &self.degradations {
+            let start = offset(period.start);
+            paint(start, start + as_secs_f64(period.duration), '~');
+        }
+
+        for period in &self.downtimes {
+            let start = offset(period.start);
+            paint(start, start + as_secs_f64(period.duration), '_');
+        }
+
+        if self.state == State::Degraded {
+            paint(offset(self.since_wall), session, '~');
+        }
+
+        if let Some(ref outage) = self.outage {
+            let end = if self.state == State::Down { session } else { offset(self.since_wall) };
+            paint(offset(outage.start_wall), end, '_');
+        }
+
+        marks.into_iter().collect()
+    }
+
+    /// Availability with every second weighted by how recent it is: up
+    /// counts as 100, degraded as 50 and down as 0, and the weight halves
+    /// every half-life. So an outage a week ago barely matters while one a
+    /// minute ago weighs heavily.
+    pub fn health_score(&self) -> f64 {
+        self.decayed_health(self.clock.now().duration_since(self.last))
+    }
+
+    /// The health score after `elapsed` more time in the current state.
+    fn decayed_health(&self, elapsed: Duration) -> f64 {
+        let value = match self.state {
+            State::Up => 100.0,
+            State::Degraded => 50.0,
+            State::Down => 0.0,
+        };
+
+        let decay = 0.5f64.powf(as_secs_f64(elapsed) / as_secs_f64(self.half_life));
+        self.health * decay + value * (1.0 - decay)
+    }
+
+    /// While the link is back up but the outage has not yet been recorded,
+    /// how much longer it must stay up.
+    pub fn settling(&self) -> Option<Duration> {
+        let recovered = self.outage.as_ref().and_then(|outage| outage.recovered)?;
+        let up_for = self.clock.now().duration_since(recovered);
+
+        Some(self.settle.checked_sub(up_for).unwrap_or(Duration::from_secs(0)))
+    }
+
+    /// The outage recorded by the last `set_state`, if it recorded one.
+    /// With a settle time, this is not the call that brought the link back
+    /// but the first one after it had stayed up for that long.
+    pub fn settled(&self) -> Option<&Period> {
+        if self.settled { self.downtimes.last() } else { None }
+    }
+
+    /// How much of the startup grace period is left, if it has not ended.
+    pub fn grace_left(&self) -> Option<Duration> {
+        self.grace.checked_sub(self.session()).filter(|left| *left > Duration::from_secs(0))
+    }
+
+    /// Flags the open outage, if any, as coinciding with a change of
+    /// network interface.
+    pub fn note_local_change(&mut self) {
+        if let Some(ref mut outage) = self.outage {
+            outage.local_change = true;
+        }
+    }
+
+    /// Marks the link as down. Does nothing if it already is.
+    pub fn down(&mut self) {
+        self.set_state(State::Down);
+    }
+
+    /// Marks the link as up, returning how long the outage lasted if there
+    /// was one.
+    pub fn up(&mut self) -> Option<Duration> {
+        match self.set_state(State::Up) {
+            Some((State::Down, duration)) => Some(duration),
+            _ => None,
+        }
+    }
+
+    /// Finished outages, oldest first.
+    pub fn downtimes(&self) -> &[Period] {
+        &self.downtimes
+    }
+
+    /// Finished degradations, oldest first.
+    pub fn degradations(&self) -> &[Period] {
+        &self.degradations
+    }
+
+    /// Counts of finished outages per duration bucket.
+    pub fn histogram(&self) -> &[usize; 4] {
+        &self.histogram
+    }
+
+    /// Whether the local date has changed since the session started.
+    /// Dates are compared rather than elapsed time, so days that DST
+    /// makes 23 or 25 hours long still end at midnight.
+    pub fn is_new_day(&self) -> bool {
+        local_date(self.clock.wall()) != local_date(self.start_wall)
+    }
+
+    pub fn session(&self) -> Duration {
+        self.clock.now().duration_since(self.start)
+    }
+
+    fn total(&self, state: State, accumulated: Duration, now: Instant) -> Duration {
+        if self.state == state {
+            accumulated + now.duration_since(self.last)
+        }
+        else {
+            accumulated
+        }
+    }
+
+    pub fn total_uptime(&self) -> Duration {
+        self.total(State::Up, self.up, self.clock.now())
+    }
+
+    pub fn total_degraded(&self) -> Duration {
+        self.total(State::Degraded, self.degraded, self.clock.now())
+    }
+
+    pub fn total_downtime(&self) -> Duration {
+        self.total(State::Down, self.down, self.clock.now())
+    }
+
+    /// Share of the session spent in `state`, as a percentage.
+    pub fn percentage(&self, state: State) -> f64 {
+        let session = as_secs_f64(self.session());
+
+        if session == 0.0 {
+            return if self.state == state { 100.0 } else { 0.0 };
+        }
+
+        let total = match state {
+            State::Up => self.total_uptime(),
+            State::Degraded => self.total_degraded(),
+            State::Down => self.total_downtime(),
+        };
+
+        as_secs_f64(total) / session * 100.0
+    }
+
+    pub fn uptime_percentage(&self) -> f64 {
+        self.percentage(State::Up)
+    }
+
+    /// Number of `set_state` calls so far.
+    pub fn polls(&self) -> usize {
+        self.polls.iter().sum()
+    }
+
+    /// Share of `set_state` calls that reported the link up, as a
+    /// percentage. With one call per evenly spaced poll this should track
+    /// `uptime_percentage`; a gap between them means polls were missed or
+    /// delayed.
+    pub fn sampled_uptime_percentage(&self) -> Option<f64> {
+        match self.polls() {
+            0 => None,
+            total => Some(self.polls[State::Up as usize] as f64 / total as f64 * 100.0),
+        }
+    }
+
+    /// Share of the `set_state` calls in the recent window that reported
+    /// the link up, as a percentage.
+    pub fn recent_uptime_percentage(&self) -> Option<f64> {
+        if self.recent.is_empty() {
+            return None;
+        }
+
+        let up = self.recent.iter().filter(|&&(_, state)| state == State::Up).count();
+        Some(up as f64 / self.recent.len() as f64 * 100.0)
+    }
+
+    pub fn recent_window(&self) -> Duration {
+        self.recent_window
+    }
+
+    /// Compares the summed up, degraded and down time against the session
+    /// length, returning the discrepancy if it exceeds `tolerance`.
+    pub fn self_check(&self, tolerance: Duration) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let summed = self.total(State::Up, self.up, now)
+            + self.total(State::Degraded, self.degraded, now)
+            + self.total(State::Down, self.down, now);
+        let session = now.duration_since(self.start);
+
+        let drift = if summed > session { summed - session } else { session - summed };
+
+        if drift > tolerance {
+            return Err(drift);
+        }
+
+        Ok(())
+    }
+}
+
+/// Year and day of the year of `ts` in local time.
+fn local_date(ts: Timespec) -> (i32, i32) {
+    let tm = time::at(ts);
+    (tm.tm_year, tm.tm_yday)
+}
+
+/// Labels for the outage duration buckets delimited by `bands`.
+pub fn band_labels(bands: &[u64; 3]) -> [String; 4] {
+    let short = |secs: u64| {
+        if secs % 3600 == 0 {
+            format!("{}h", secs / 3600)
+        }
+        else if secs % 60 == 0 {
+            format!("{}m", secs / 60)
+        }
+        else {
+            format!("{}s", secs)
+        }
+    };
+
+    [
+        format!("<{}", short(bands[0])),
+        format!("{}-{}", short(bands[0]), short(bands[1])),
+        format!("{}-{}", short(bands[1]), short(bands[2])),
+        format!(">{}", short(bands[2])),
+    ]
+}
+
+pub fn as_secs_f64(dur: Duration) -> f64 {
+    dur.as_secs() as f64 + dur.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use std::rc::Rc;
+
+    #[test]
+    fn timeline_marks_open_and_recorded_outages() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+
+        tracker.set_state(State::Up);
+        clock.advance(Duration::from_secs(5));
+        tracker.set_state(State::Down);
+        clock.advance(Duration::from_secs(5));
+
+        assert_eq!(tracker.timeline(10), "#####_____");
+
+        tracker.set_state(State::Up);
+        clock.advance(Duration::from_secs(10));
+        tracker.set_state(State::Up);
+
+        assert_eq!(tracker.downtimes().len(), 1);
+        assert_eq!(tracker.timeline(4), "#_##");
+    }
+
+    #[test]
+    fn health_recovers_after_outage() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+        tracker.set_half_life(Duration::from_secs(60));
+
+        clock.advance(Duration::from_secs(600));
+        tracker.set_state(State::Down);
+        assert!(tracker.health_score() > 99.9);
+
+        clock.advance(Duration::from_secs(60));
+        tracker.set_state(State::Up);
+        let after_outage = tracker.health_score();
+        assert!((after_outage - 50.0).abs() < 0.1);
+
+        clock.advance(Duration::from_secs(60));
+        let minute_later = tracker.health_score();
+        assert!(minute_later > after_outage);
+        assert!((minute_later - 75.0).abs() < 0.1);
+
+        clock.advance(Duration::from_secs(600));
+        assert!(tracker.health_score() > 99.9);
+    }
+
+    #[test]
+    fn ignores_failures_during_grace() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+        tracker.set_grace(Duration::from_secs(30));
+
+        tracker.set_state(State::Down);
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(tracker.set_state(State::Down), None);
+        assert_eq!(tracker.state(), State::Up);
+        clock.advance(Duration::from_secs(5));
+        tracker.set_state(State::Up);
+
+        assert!(tracker.downtimes().is_empty());
+        assert_eq!(tracker.total_downtime(), Duration::from_secs(0));
+        assert_eq!(tracker.uptime_percentage(), 100.0);
+
+        clock.advance(Duration::from_secs(10));
+        tracker.set_state(State::Down);
+        assert!(tracker.is_down());
+    }
+
+    #[test]
+    fn totals_add_up_to_the_session() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+        tracker.set_settle(Duration::from_secs(3));
+        let states = [State::Up, State::Down, State::Degraded, State::Down, State::Up];
+
+        for i in 0..500u64 {
+            clock.advance(Duration::from_millis(137 + i * 7919 % 1013));
+            tracker.set_state(states[(i * i % 11) as usize % states.len()]);
+
+            assert_eq!(tracker.self_check(Duration::from_secs(0)), Ok(()));
+        }
+
+        clock.advance(Duration::from_millis(333));
+        assert_eq!(tracker.self_check(Duration::from_secs(0)), Ok(()));
+        assert_eq!(tracker.total_uptime() + tracker.total_degraded() + tracker.total_downtime(),
+            tracker.session());
+    }
+
+    #[test]
+    fn timeline_prefers_down_over_degraded() {
+        let clock = Rc::new(MockClock::new());
+        let mut tracker = TimeTracker::new(clock.clone());
+
+        tracker.set_state(State::Degraded);
+        clock.advance(Duration::from_secs(2));
+        tracker.set_state(State::Down);
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(tracker.timeline(4), "~~__");
+    }
+}
@@ -0,0 +1,38 @@
+use std::net::{IpAddr, ToSocketAddrs};
+
+/// Why `host` cannot be pinged as written, if it cannot. Only the form is
+/// checked; see `resolves` for names. The reason does not repeat the host,
+/// so that callers can name it as they see fit.
+pub fn problem(host: &str) -> Option<&'static str> {
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    if host.contains("://") {
+        return Some("only hostnames and IP addresses can be pinged, not URLs");
+    }
+
+    if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Some("not a valid IPv4 address");
+    }
+
+    if host.contains(':') {
+        return Some("not a valid IPv6 address");
+    }
+
+    let valid_label = |label: &str| {
+        !label.is_empty() && label.len() <= 63 && !label.starts_with('-') && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    if host.len() > 253 || !host.trim_end_matches('.').split('.').all(valid_label) {
+        return Some("not a valid hostname");
+    }
+
+    None
+}
+
+/// Looks `host` up, returning the resolver's error if that fails.
+pub fn resolves(host: &str) -> Result<(), String> {
+    (host, 0).to_socket_addrs().map(|_| ()).map_err(|e| e.to_string())
+}